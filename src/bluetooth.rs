@@ -1,239 +1,199 @@
-﻿use crate::err;
-#[cfg(feature = "debug")]
+//! Platform-specific Bluetooth RFCOMM backends.
+//! The backend matching the target operating system is selected at compile time,
+//! the device selection logic is shared by all backends.
+use crate::error::EdifierError;
 use crate::utils::join_hex;
-use std::mem::zeroed;
-use windows::Win32::Devices::Bluetooth::{
-    BluetoothEnumerateInstalledServices, BluetoothFindDeviceClose, BluetoothFindFirstDevice, BluetoothFindFirstRadio,
-    BluetoothFindNextDevice, BluetoothFindNextRadio, BluetoothFindRadioClose, BluetoothSetServiceState,
-    AF_BTH, BLUETOOTH_DEVICE_INFO, BLUETOOTH_DEVICE_SEARCH_PARAMS,
-    BLUETOOTH_FIND_RADIO_PARAMS, BLUETOOTH_SERVICE_DISABLE, BLUETOOTH_SERVICE_ENABLE,
-    BTHPROTO_RFCOMM, BTH_ERROR_SUCCESS, SOCKADDR_BTH,
-};
-use windows::Win32::Foundation::HANDLE;
-use windows::Win32::Networking::WinSock;
-use windows::Win32::Networking::WinSock::{
-    WSACleanup, WSAGetLastError, WSAStartup, INVALID_SOCKET, SEND_RECV_FLAGS, SOCKADDR, SOCKET,
-    SOCKET_ERROR, SOCK_STREAM, WSADATA, WSAETIMEDOUT,
-};
-use windows_core::GUID;
-
-type BluetoothDevice = (HANDLE, BLUETOOTH_DEVICE_INFO);
-
-const WINSOCK_VERSION_2_2: u16 = 0x0202;
-const A2DP_SINK_UUID: GUID = GUID::from_u128(0x0000110B_0000_1000_8000_00805F9B34FB);
-const HFP_AG_UUID: GUID = GUID::from_u128(0x0000111E_0000_1000_8000_00805F9B34FB);
-const AUDIO_SERVICE_UUIDS: [GUID; 2] = [A2DP_SINK_UUID, HFP_AG_UUID];
-
-/// Resets Bluetooth audio-related services for the device that provides the specified SPP service.
-pub(crate) fn pair(spp_guid: &GUID) -> Result<(), String> {
-    let (radio_handle, device_info) = find_device(spp_guid)?;
-
-    for service_guid in AUDIO_SERVICE_UUIDS {
-        reset_bluetooth_service(radio_handle, &device_info, &service_guid)?;
-    }
-
-    Ok(())
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::SystemTime;
+
+#[cfg(target_os = "linux")]
+mod bluez;
+#[cfg(target_os = "linux")]
+mod hci;
+#[cfg(target_os = "linux")]
+mod sdp;
+#[cfg(windows)]
+mod winsock;
+
+#[cfg(target_os = "linux")]
+pub(crate) use bluez::{list_devices, pair, scan_devices};
+#[cfg(target_os = "linux")]
+pub use bluez::BluetoothTransport;
+#[cfg(windows)]
+pub(crate) use winsock::{list_devices, pair, scan_devices};
+#[cfg(windows)]
+pub use winsock::BluetoothTransport;
+
+#[cfg(not(any(windows, target_os = "linux")))]
+compile_error!("Bluetooth backend is available for Windows and Linux only.");
+
+/// Device providing the Edifier SPP service as known to the operating system.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct DeviceInfo {
+    /// Device address in display order.
+    pub address: [u8; 6],
+    /// Device friendly name, if known.
+    pub name: Option<String>,
+    /// The device is currently connected.
+    pub connected: bool,
+    /// The device is remembered by the system.
+    pub remembered: bool,
+    /// The device is paired with a link key.
+    pub authenticated: bool,
+    /// When the device was last seen, if the system tracks it.
+    pub last_seen: Option<SystemTime>,
+    /// When the device was last used, if the system tracks it.
+    pub last_used: Option<SystemTime>,
 }
 
-/// Opens an RFCOMM Bluetooth socket connection to the device that provides the specified SPP service.
-pub(crate) fn connect(spp_guid: &GUID) -> Result<SOCKET, String> {
-    unsafe {
-        startup_winsock()?;
-
-        let socket = WinSock::socket(AF_BTH as i32, SOCK_STREAM, BTHPROTO_RFCOMM as i32)
-            .map_err(|e| e.to_string())?;
-        if socket == INVALID_SOCKET {
-            return err!("Invalid socket.");
-        }
-
-        let (_radio, device_info) = find_device(spp_guid)?;
-        let address = bluetooth_socket_address(&device_info, spp_guid);
+impl DeviceInfo {
+    /// Returns the device address formatted as `XX:XX:XX:XX:XX:XX`.
+    pub fn address_string(&self) -> String {
+        format_address(self.address)
+    }
+}
 
-        let connect_result = WinSock::connect(
-            socket,
-            &address as *const SOCKADDR_BTH as *const SOCKADDR,
-            size_of::<SOCKADDR_BTH>() as i32,
-        );
+/// Selects one of several devices providing the Edifier SPP service.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DeviceSelector {
+    /// Device address in the `XX:XX:XX:XX:XX:XX` display order.
+    Address([u8; 6]),
+    /// Device friendly name, compared case-insensitively.
+    Name(String),
+}
 
-        if connect_result == SOCKET_ERROR {
-            let error = WSAGetLastError();
-            return if error == WSAETIMEDOUT {
-                err!("Unable to connect to device.")
-            } else {
-                err!("Failed to connect to device: {error:?}.")
-            };
+impl DeviceSelector {
+    /// Checks if the device with the address in display order and the optional name
+    /// matches the selector.
+    pub fn matches(&self, address: [u8; 6], name: Option<&str>) -> bool {
+        match self {
+            DeviceSelector::Address(a) => *a == address,
+            DeviceSelector::Name(n) => name.is_some_and(|name| name.trim().eq_ignore_ascii_case(n)),
         }
-
-        Ok(socket)
     }
 }
 
-/// Closes the Bluetooth socket and cleans up the WinSock session.
-pub(crate) fn disconnect(socket: SOCKET) {
-    unsafe {
-        WinSock::closesocket(socket);
-        WSACleanup();
+impl Display for DeviceSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeviceSelector::Address(address) => write!(f, "{}", format_address(*address)),
+            DeviceSelector::Name(name) => write!(f, "{name}"),
+        }
     }
 }
 
-/// Sends raw data over the Bluetooth socket and returns the response bytes.
-pub(crate) fn send(socket: SOCKET, data: &[u8]) -> Result<Vec<u8>, String> {
-    #[cfg(feature = "debug")]
-    println!("BTQ: [{}]", join_hex(&data, ", "));
-
-    let result = unsafe {
-        let bytes_sent = WinSock::send(socket, data, SEND_RECV_FLAGS(0));
-        if bytes_sent == SOCKET_ERROR {
-            let error = WSAGetLastError();
-            return err!("Write error: {error:?}.",);
-        }
+impl FromStr for DeviceSelector {
+    type Err = EdifierError;
 
-        let mut buffer = [0u8; 256];
-        let bytes_read = WinSock::recv(socket, &mut buffer, SEND_RECV_FLAGS(0));
-        if bytes_read == SOCKET_ERROR {
-            let error = WSAGetLastError();
-            return err!("Read error: {error:?}.");
+    /// Parses a MAC address, any other non-empty string is taken as a device name.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(EdifierError::InvalidValue(
+                "Device MAC address or name must not be empty.".to_string(),
+            ));
         }
 
-        buffer[..bytes_read as usize].to_vec()
-    };
-
-    #[cfg(feature = "debug")]
-    println!("BTR: [{}]", join_hex(&result, ", "));
-
-    Ok(result)
+        Ok(match parse_address(s) {
+            Some(address) => DeviceSelector::Address(address),
+            None => DeviceSelector::Name(s.to_string()),
+        })
+    }
 }
 
-fn startup_winsock() -> Result<(), String> {
-    unsafe {
-        let mut data: WSADATA = zeroed();
-        let result = WSAStartup(WINSOCK_VERSION_2_2, &mut data);
-        if result != 0 {
-            return err!("WSA startup failed: ERROR ({result}).");
-        }
-    }
-    Ok(())
+/// Parses a `XX:XX:XX:XX:XX:XX` or `XX-XX-XX-XX-XX-XX` address in display order.
+pub(crate) fn parse_address(s: &str) -> Option<[u8; 6]> {
+    let bytes = s
+        .split([':', '-'])
+        .map(|part| {
+            (part.len() == 2)
+                .then(|| u8::from_str_radix(part, 16).ok())
+                .flatten()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    bytes.try_into().ok()
 }
 
-fn reset_bluetooth_service(
-    radio_handle: HANDLE,
-    device_info: &BLUETOOTH_DEVICE_INFO,
-    service_guid: &GUID,
-) -> Result<(), String> {
-    unsafe {
-        BluetoothSetServiceState(
-            Some(radio_handle),
-            device_info,
-            service_guid,
-            BLUETOOTH_SERVICE_DISABLE,
-        );
+/// Formats an address in display order as `XX:XX:XX:XX:XX:XX`.
+pub(crate) fn format_address(address: [u8; 6]) -> String {
+    join_hex(address, ":")
+}
 
-        let result = BluetoothSetServiceState(
-            Some(radio_handle),
-            device_info,
-            service_guid,
-            BLUETOOTH_SERVICE_ENABLE,
-        );
+/// Checks if the device name carries the Edifier brand, which identifies devices
+/// whose services are not known before pairing.
+pub(crate) fn is_edifier_name(name: &str) -> bool {
+    name.to_ascii_uppercase().contains("EDIFIER")
+}
 
-        if result != BTH_ERROR_SUCCESS {
-            return err!("Bluetooth reset state failed: {result}.");
-        }
+/// Returns the only device matching the selector, failing when there are none or several.
+fn select_device<T>(
+    mut devices: Vec<T>,
+    selector: &DeviceSelector,
+    describe: impl Fn(&T) -> String,
+) -> Result<T, EdifierError> {
+    match devices.len() {
+        0 => Err(EdifierError::NoMatchingDevice(selector.to_string())),
+        1 => Ok(devices.remove(0)),
+        _ => Err(EdifierError::AmbiguousDevice {
+            selector: selector.to_string(),
+            candidates: devices.iter().map(describe).collect(),
+        }),
     }
-
-    Ok(())
 }
 
-fn bluetooth_socket_address(
-    device_info: &BLUETOOTH_DEVICE_INFO,
-    service_guid: &GUID,
-) -> SOCKADDR_BTH {
-    let mut address: SOCKADDR_BTH = unsafe { zeroed() };
-    address.addressFamily = AF_BTH;
-    address.btAddr = unsafe { device_info.Address.Anonymous.ullLong };
-    address.serviceClassId = *service_guid;
-    address
-}
+#[cfg(test)]
+mod test {
+    use super::*;
 
-/// Checks if the device has the specified service enabled.
-fn device_has_service(
-    radio_handle: HANDLE,
-    device_info: &BLUETOOTH_DEVICE_INFO,
-    service_guid: &GUID,
-) -> bool {
-    let mut service_guids = [GUID::default(); 10];
-    let mut service_guid_count = service_guids.len() as u32;
-
-    let result = unsafe {
-        BluetoothEnumerateInstalledServices(
-            radio_handle.into(),
-            device_info,
-            &mut service_guid_count,
-            service_guids.as_mut_ptr().into(),
-        )
-    };
-
-    result == BTH_ERROR_SUCCESS
-        && service_guids[..service_guid_count as usize]
-            .iter()
-            .any(|installed_service_guid| installed_service_guid == service_guid)
-}
+    const ADDRESS: [u8; 6] = [0x00, 0x1E, 0x7C, 0x12, 0x34, 0x56];
 
-/// Searches for the first Bluetooth device that provides the service matching the specified UUID
-fn find_device(service_guid: &GUID) -> Result<BluetoothDevice, String> {
-    let find_radio_params = BLUETOOTH_FIND_RADIO_PARAMS {
-        dwSize: size_of::<BLUETOOTH_FIND_RADIO_PARAMS>() as u32,
-    };
-    let mut radio_handle = HANDLE::default();
-
-    unsafe {
-        let find_radio_handle = BluetoothFindFirstRadio(&find_radio_params, &mut radio_handle)
-            .map_err(|e| e.to_string())?;
-        if find_radio_handle.is_invalid() {
-            return err!("No Bluetooth radio.");
-        }
+    #[test]
+    fn test_parse_selector() {
+        assert_eq!(
+            "00:1E:7C:12:34:56".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Address(ADDRESS)
+        );
+        assert_eq!(
+            "00-1e-7c-12-34-56".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Address(ADDRESS)
+        );
+        assert_eq!(
+            " EDIFIER W820NB ".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Name("EDIFIER W820NB".to_string())
+        );
+        assert_eq!(
+            "00:1E:7C:12:34".parse::<DeviceSelector>().unwrap(),
+            DeviceSelector::Name("00:1E:7C:12:34".to_string())
+        );
+        assert!("  ".parse::<DeviceSelector>().is_err());
+        assert_eq!(format_address(ADDRESS), "00:1E:7C:12:34:56");
+    }
 
-        let device_search_params = BLUETOOTH_DEVICE_SEARCH_PARAMS {
-            dwSize: size_of::<BLUETOOTH_DEVICE_SEARCH_PARAMS>() as u32,
-            fReturnAuthenticated: true.into(),
-            fReturnRemembered: true.into(),
-            fReturnUnknown: true.into(),
-            fReturnConnected: true.into(),
-            fIssueInquiry: false.into(),
-            cTimeoutMultiplier: 2,
-            hRadio: radio_handle,
-        };
-
-        'radios: loop {
-            let mut device_info = BLUETOOTH_DEVICE_INFO {
-                dwSize: size_of::<BLUETOOTH_DEVICE_INFO>() as u32,
-                ..Default::default()
-            };
-
-            let find_device_handle =
-                BluetoothFindFirstDevice(&device_search_params, &mut device_info)
-                    .map_err(|e| e.to_string())?;
-
-            if !find_device_handle.is_invalid() {
-                'devices: loop {
-                    if device_has_service(radio_handle, &device_info, service_guid) {
-                        return Ok((radio_handle, device_info));
-                    }
-
-                    if BluetoothFindNextDevice(find_device_handle, &mut device_info).is_err() {
-                        break 'devices;
-                    }
-                }
-
-                BluetoothFindDeviceClose(find_device_handle).map_err(|e| e.to_string())?;
-            }
-
-            if BluetoothFindNextRadio(find_radio_handle, &mut radio_handle).is_err() {
-                break 'radios;
-            }
-        }
+    #[test]
+    fn test_selector_matches() {
+        let by_address = DeviceSelector::Address(ADDRESS);
+        let by_name = DeviceSelector::Name("edifier w820nb".to_string());
 
-        BluetoothFindRadioClose(find_radio_handle).map_err(|e| e.to_string())?;
+        assert!(by_address.matches(ADDRESS, None));
+        assert!(!by_address.matches([0; 6], Some("EDIFIER W820NB")));
+        assert!(by_name.matches([0; 6], Some("EDIFIER W820NB")));
+        assert!(!by_name.matches(ADDRESS, None));
     }
 
-    err!("No devices found.")
+    #[test]
+    fn test_select_device() {
+        let selector = DeviceSelector::Name("W820NB".to_string());
+
+        assert_eq!(select_device(vec![1], &selector, u8::to_string).unwrap(), 1);
+        assert!(matches!(
+            select_device(Vec::<u8>::new(), &selector, u8::to_string),
+            Err(EdifierError::NoMatchingDevice(_))
+        ));
+        assert!(matches!(
+            select_device(vec![1, 2], &selector, u8::to_string),
+            Err(EdifierError::AmbiguousDevice { candidates, .. }) if candidates == ["1", "2"]
+        ));
+    }
 }
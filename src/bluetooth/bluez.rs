@@ -0,0 +1,569 @@
+//! BlueZ RFCOMM backend built on Linux Bluetooth sockets.
+use super::{
+    format_address, hci, is_edifier_name, parse_address, sdp, select_device, DeviceInfo,
+    DeviceSelector,
+};
+use crate::decoder::FrameDecoder;
+use crate::error::EdifierError;
+use crate::message::EdifierMessage;
+use crate::transport::Transport;
+#[cfg(feature = "debug")]
+use crate::utils::join_hex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::time::{Duration, Instant, SystemTime};
+
+const BTPROTO_HCI: i32 = 1;
+const BTPROTO_RFCOMM: i32 = 3;
+const HCI_GET_CONN_LIST: libc::c_ulong = 0x800448D4;
+const HCI_MAX_CONNECTIONS: usize = 10;
+const SYSFS_BLUETOOTH_PATH: &str = "/sys/class/bluetooth";
+const BLUEZ_STORAGE_PATH: &str = "/var/lib/bluetooth";
+const NAME_REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[repr(C)]
+struct SockAddrRc {
+    rc_family: libc::sa_family_t,
+    rc_bdaddr: [u8; 6],
+    rc_channel: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct HciConnInfo {
+    handle: u16,
+    bdaddr: [u8; 6],
+    link_type: u8,
+    out: u8,
+    state: u16,
+    link_mode: u32,
+}
+
+#[repr(C)]
+struct HciConnListRequest {
+    dev_id: u16,
+    conn_num: u16,
+    conn_info: [HciConnInfo; HCI_MAX_CONNECTIONS],
+}
+
+/// Pairing is handled by the BlueZ daemon on Linux.
+pub(crate) fn pair(
+    _spp_uuid: u128,
+    _selector: Option<&DeviceSelector>,
+) -> Result<(), EdifierError> {
+    Err(EdifierError::Unsupported(
+        "Pairing is not supported by the BlueZ backend, use `bluetoothctl` instead.",
+    ))
+}
+
+/// Lists the connected and paired devices that provide the specified SPP service.
+/// Services of the paired devices are taken from the BlueZ storage, connected devices
+/// unknown to it are queried over SDP. BlueZ does not record last seen and used times.
+pub(crate) fn list_devices(spp_uuid: u128) -> Result<Vec<DeviceInfo>, EdifierError> {
+    let radios = find_radios();
+    if radios.is_empty() {
+        return Err(EdifierError::NoRadio);
+    }
+
+    let mut connected = BTreeSet::new();
+    for dev_id in radios {
+        connected.extend(connected_devices(dev_id));
+    }
+    /* The BlueZ storage is readable by root only on most installs, connected devices
+    are still listed then and the error is reported only when nothing else is found. */
+    let paired = paired_devices();
+    let mut addresses: BTreeSet<_> =
+        paired.as_deref().unwrap_or_default().iter().copied().collect();
+    addresses.extend(connected.iter().copied());
+
+    let service = format_uuid(spp_uuid);
+    let mut devices = Vec::new();
+    for bdaddr in addresses {
+        let info = device_info_file(bdaddr);
+        let has_service = match &info {
+            Some(info) => info_has_service(info, &service),
+            None => matches!(sdp::find_rfcomm_channel(bdaddr, spp_uuid), Ok(Some(_))),
+        };
+        if !has_service {
+            continue;
+        }
+
+        devices.push(DeviceInfo {
+            address: display_address(bdaddr),
+            name: info.as_deref().and_then(info_name),
+            connected: connected.contains(&bdaddr),
+            remembered: info.is_some(),
+            authenticated: info.as_ref().is_some_and(|info| info.contains("[LinkKey]")),
+            last_seen: None,
+            last_used: None,
+        });
+    }
+
+    if devices.is_empty() {
+        paired?;
+    }
+
+    Ok(devices)
+}
+
+/// Runs an inquiry on every radio and returns the nearby devices that look like Edifier
+/// headsets: named by the vendor, or known to BlueZ as providing the specified SPP service.
+pub(crate) fn scan_devices(
+    spp_uuid: u128,
+    timeout: Duration,
+) -> Result<Vec<DeviceInfo>, EdifierError> {
+    let radios = find_radios();
+    if radios.is_empty() {
+        return Err(EdifierError::NoRadio);
+    }
+
+    let service = format_uuid(spp_uuid);
+    let mut devices = BTreeMap::new();
+    for dev_id in radios {
+        let connected = connected_devices(dev_id);
+
+        for inquiry_info in hci::inquiry(dev_id, timeout)? {
+            let bdaddr = inquiry_info.bdaddr;
+            if devices.contains_key(&bdaddr) {
+                continue;
+            }
+
+            let info = device_info_file(bdaddr);
+            let name = hci::read_remote_name(dev_id, &inquiry_info, NAME_REQUEST_TIMEOUT)
+                .or_else(|| info.as_deref().and_then(info_name));
+
+            let has_service = info.as_deref().is_some_and(|i| info_has_service(i, &service));
+            if !has_service && !name.as_deref().is_some_and(is_edifier_name) {
+                continue;
+            }
+
+            devices.insert(
+                bdaddr,
+                DeviceInfo {
+                    address: display_address(bdaddr),
+                    name,
+                    connected: connected.contains(&bdaddr),
+                    remembered: info.is_some(),
+                    authenticated: info.as_ref().is_some_and(|info| info.contains("[LinkKey]")),
+                    last_seen: Some(SystemTime::now()),
+                    last_used: None,
+                },
+            );
+        }
+    }
+
+    Ok(devices.into_values().collect())
+}
+
+/// RFCOMM Bluetooth socket connection to the device SPP service.
+#[derive(Debug)]
+pub struct BluetoothTransport {
+    fd: i32,
+    bdaddr: [u8; 6],
+    channel: u8,
+    decoder: FrameDecoder,
+}
+
+impl BluetoothTransport {
+    /// Opens an RFCOMM Bluetooth socket connection to the device that provides the specified SPP service.
+    pub fn connect(spp_uuid: u128) -> Result<Self, EdifierError> {
+        Self::open(spp_uuid, None)
+    }
+
+    /// Opens an RFCOMM Bluetooth socket connection to the device matching the selector
+    /// among the devices that provide the specified SPP service.
+    pub fn connect_to(spp_uuid: u128, selector: &DeviceSelector) -> Result<Self, EdifierError> {
+        Self::open(spp_uuid, Some(selector))
+    }
+
+    fn open(spp_uuid: u128, selector: Option<&DeviceSelector>) -> Result<Self, EdifierError> {
+        let (bdaddr, channel) = find_device(spp_uuid, selector)?;
+
+        Ok(Self {
+            fd: open_socket(bdaddr, channel)?,
+            bdaddr,
+            channel,
+            decoder: FrameDecoder::new(),
+        })
+    }
+}
+
+impl Transport for BluetoothTransport {
+    /// Sends raw frame bytes over the Bluetooth socket, until the whole frame is written.
+    fn send(&mut self, message: &EdifierMessage, timeout: Duration) -> Result<(), EdifierError> {
+        #[cfg(feature = "debug")]
+        println!("BTQ: [{}]", join_hex(message.as_slice(), ", "));
+
+        let deadline = Instant::now() + timeout;
+        let mut data = message.as_slice();
+        while !data.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(EdifierError::Timeout);
+            }
+            set_socket_timeout(self.fd, libc::SO_SNDTIMEO, remaining)?;
+
+            let bytes_sent = unsafe { libc::send(self.fd, data.as_ptr().cast(), data.len(), 0) };
+            if bytes_sent < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            if bytes_sent == 0 {
+                return Err(EdifierError::Disconnected);
+            }
+
+            data = &data[bytes_sent as usize..];
+        }
+
+        Ok(())
+    }
+
+    /// Reads from the Bluetooth socket until a complete frame is reassembled.
+    fn receive(&mut self, timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(message) = self.decoder.next_message() {
+                return Ok(message);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let flags = if remaining.is_zero() {
+                /* Only take the bytes already received, fails with EAGAIN otherwise. */
+                libc::MSG_DONTWAIT
+            } else {
+                set_socket_timeout(self.fd, libc::SO_RCVTIMEO, remaining)?;
+                0
+            };
+
+            let mut buffer = [0u8; 256];
+            let bytes_read =
+                unsafe { libc::recv(self.fd, buffer.as_mut_ptr().cast(), buffer.len(), flags) };
+            if bytes_read < 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            if bytes_read == 0 {
+                return Err(EdifierError::Disconnected);
+            }
+
+            let chunk = &buffer[..bytes_read as usize];
+
+            #[cfg(feature = "debug")]
+            println!("BTR: [{}]", join_hex(chunk, ", "));
+
+            self.decoder.push(chunk);
+        }
+    }
+
+    /// Closes the Bluetooth socket.
+    fn close(&mut self) {
+        if self.fd < 0 {
+            return;
+        }
+
+        unsafe { libc::close(self.fd) };
+        self.fd = -1;
+    }
+
+    /// Closes the Bluetooth socket and connects again to the same device and channel.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        self.close();
+        self.fd = open_socket(self.bdaddr, self.channel)?;
+        self.decoder = FrameDecoder::new();
+
+        Ok(())
+    }
+}
+
+impl Drop for BluetoothTransport {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Connects an RFCOMM socket to the channel of the device.
+fn open_socket(bdaddr: [u8; 6], channel: u8) -> Result<i32, EdifierError> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_BLUETOOTH,
+            libc::SOCK_STREAM | libc::SOCK_CLOEXEC,
+            BTPROTO_RFCOMM,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let address = SockAddrRc {
+        rc_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+        rc_bdaddr: bdaddr,
+        rc_channel: channel,
+    };
+
+    let connect_result = unsafe {
+        libc::connect(
+            fd,
+            &address as *const SockAddrRc as *const libc::sockaddr,
+            size_of::<SockAddrRc>() as libc::socklen_t,
+        )
+    };
+
+    if connect_result < 0 {
+        let error = io::Error::last_os_error();
+        unsafe { libc::close(fd) };
+        return if error.kind() == io::ErrorKind::TimedOut {
+            Err(EdifierError::Timeout)
+        } else {
+            Err(EdifierError::ConnectFailed(error))
+        };
+    }
+
+    Ok(fd)
+}
+
+/// Sets the socket send or receive timeout.
+fn set_socket_timeout(fd: i32, option: i32, timeout: Duration) -> Result<(), EdifierError> {
+    /* Zero timeval means no timeout at all. */
+    let timeout = timeout.max(Duration::from_micros(1));
+    let value = libc::timeval {
+        tv_sec: timeout.as_secs() as libc::time_t,
+        tv_usec: timeout.subsec_micros() as libc::suseconds_t,
+    };
+
+    let result = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            option,
+            &value as *const libc::timeval as *const libc::c_void,
+            size_of::<libc::timeval>() as libc::socklen_t,
+        )
+    };
+    if result < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+/// Searches connected and paired devices for the one that provides the service matching
+/// the specified UUID and returns its address with the service RFCOMM channel.
+/// Without a selector the first device found is returned.
+fn find_device(
+    service_uuid: u128,
+    selector: Option<&DeviceSelector>,
+) -> Result<([u8; 6], u8), EdifierError> {
+    let radios = find_radios();
+    if radios.is_empty() {
+        return Err(EdifierError::NoRadio);
+    }
+
+    let mut connected = BTreeMap::new();
+    for dev_id in radios {
+        connected.extend(connected_devices(dev_id).into_iter().map(|bdaddr| (bdaddr, dev_id)));
+    }
+    let paired = paired_devices();
+    let mut addresses: BTreeSet<_> = connected.keys().copied().collect();
+    addresses.extend(paired.as_deref().unwrap_or_default());
+
+    let Some(selector) = selector else {
+        let device = addresses.into_iter().find_map(|bdaddr| {
+            let channel = sdp::find_rfcomm_channel(bdaddr, service_uuid).ok()??;
+            Some((bdaddr, channel))
+        });
+        return match device {
+            Some(device) => Ok(device),
+            None => Err(paired.err().unwrap_or(EdifierError::DeviceNotFound)),
+        };
+    };
+
+    let mut devices = Vec::new();
+    for bdaddr in addresses {
+        /* The stored name needs root, connected devices can be asked for it instead. */
+        let name = device_name(bdaddr).or_else(|| {
+            let dev_id = *connected.get(&bdaddr)?;
+            let info = hci::InquiryInfo::from_address(bdaddr);
+            hci::read_remote_name(dev_id, &info, NAME_REQUEST_TIMEOUT)
+        });
+        if !selector.matches(display_address(bdaddr), name.as_deref()) {
+            continue;
+        }
+
+        if let Ok(Some(channel)) = sdp::find_rfcomm_channel(bdaddr, service_uuid) {
+            devices.push((bdaddr, channel, name));
+        }
+    }
+
+    if devices.is_empty() {
+        paired?;
+    }
+
+    let (bdaddr, channel, _) = select_device(devices, selector, |(bdaddr, _, name)| {
+        let address = format_address(display_address(*bdaddr));
+        match name {
+            Some(name) => format!("{name} ({address})"),
+            None => address,
+        }
+    })?;
+
+    Ok((bdaddr, channel))
+}
+
+/// Returns identifiers of the HCI radios known to the kernel.
+fn find_radios() -> Vec<u16> {
+    let Ok(entries) = fs::read_dir(SYSFS_BLUETOOTH_PATH) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_prefix("hci")?.parse().ok()
+        })
+        .collect()
+}
+
+/// Returns addresses of the devices that have an active connection with the radio.
+fn connected_devices(dev_id: u16) -> Vec<[u8; 6]> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_BLUETOOTH,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            BTPROTO_HCI,
+        )
+    };
+    if fd < 0 {
+        return Vec::new();
+    }
+
+    let mut request = HciConnListRequest {
+        dev_id,
+        conn_num: HCI_MAX_CONNECTIONS as u16,
+        conn_info: [HciConnInfo::default(); HCI_MAX_CONNECTIONS],
+    };
+
+    let result = unsafe { libc::ioctl(fd, HCI_GET_CONN_LIST, &mut request) };
+    unsafe { libc::close(fd) };
+    if result < 0 {
+        return Vec::new();
+    }
+
+    request.conn_info[..request.conn_num as usize]
+        .iter()
+        .map(|info| info.bdaddr)
+        .collect()
+}
+
+/// Returns addresses of the devices remembered by the BlueZ daemon.
+/// Missing storage means no paired devices, other errors are returned with its path.
+fn paired_devices() -> Result<Vec<[u8; 6]>, EdifierError> {
+    let storage_error = |e: io::Error| {
+        let message = format!("{BLUEZ_STORAGE_PATH}: {e}");
+        EdifierError::Io(io::Error::new(e.kind(), message))
+    };
+
+    let adapters = match fs::read_dir(BLUEZ_STORAGE_PATH) {
+        Ok(adapters) => adapters,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(storage_error(e)),
+    };
+
+    let mut devices = Vec::new();
+    for adapter in adapters {
+        let path = adapter.map_err(storage_error)?.path();
+        if !path.is_dir() {
+            continue;
+        }
+
+        for device in fs::read_dir(path).map_err(storage_error)? {
+            let name = device.map_err(storage_error)?.file_name();
+            devices.extend(name.to_str().and_then(parse_bdaddr));
+        }
+    }
+
+    Ok(devices)
+}
+
+/// Returns the name of the device remembered by the BlueZ daemon.
+fn device_name(bdaddr: [u8; 6]) -> Option<String> {
+    info_name(&device_info_file(bdaddr)?)
+}
+
+/// Returns the content of the device `info` file kept by the BlueZ daemon.
+fn device_info_file(bdaddr: [u8; 6]) -> Option<String> {
+    let address = format_address(display_address(bdaddr));
+    let adapters = fs::read_dir(BLUEZ_STORAGE_PATH).ok()?;
+
+    adapters
+        .filter_map(|adapter| {
+            let info = adapter.ok()?.path().join(&address).join("info");
+            fs::read_to_string(info).ok()
+        })
+        .next()
+}
+
+/// Returns the device name from the `info` file, preferring the user alias.
+fn info_name(info: &str) -> Option<String> {
+    info_value(info, "Alias").or_else(|| info_value(info, "Name"))
+}
+
+/// Returns the value of the `info` file key.
+fn info_value(info: &str, key: &str) -> Option<String> {
+    info.lines().find_map(|line| {
+        let (k, v) = line.split_once('=')?;
+        (k.trim() == key).then(|| v.trim().to_string())
+    })
+}
+
+/// Checks if the `info` file lists the service with the UUID in the BlueZ storage form.
+fn info_has_service(info: &str, service: &str) -> bool {
+    info_value(info, "Services").is_some_and(|services| services.to_lowercase().contains(service))
+}
+
+/// Formats the UUID in the lowercase `8-4-4-4-12` form used by the BlueZ storage.
+fn format_uuid(uuid: u128) -> String {
+    let hex = format!("{uuid:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// Parses a `XX:XX:XX:XX:XX:XX` address into the little-endian `bdaddr_t` layout.
+fn parse_bdaddr(s: &str) -> Option<[u8; 6]> {
+    parse_address(s).map(display_address)
+}
+
+/// Converts between the little-endian `bdaddr_t` layout and the display order.
+fn display_address(mut bdaddr: [u8; 6]) -> [u8; 6] {
+    bdaddr.reverse();
+    bdaddr
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_info_file() {
+        let info = "[General]\nName=EDIFIER W820NB\nServices=0000110b-0000-1000-8000-00805f9b34fb;\
+            edf00000-edfe-dfed-fedf-edfedfedfedf;\n\n[LinkKey]\nKey=00112233\n";
+
+        assert_eq!(info_name(info).unwrap(), "EDIFIER W820NB");
+        assert_eq!(
+            info_name(&format!("Alias=Office\n{info}")).unwrap(),
+            "Office"
+        );
+        assert!(
+            info_value(info, "Services")
+                .unwrap()
+                .contains(&format_uuid(0xEDF00000_EDFE_DFED_FEDF_EDFEDFEDFEDF))
+        );
+        assert_eq!(parse_bdaddr("00:1E:7C:12:34:56").unwrap(), [0x56, 0x34, 0x12, 0x7C, 0x1E, 0x00]);
+    }
+}
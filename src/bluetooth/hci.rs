@@ -0,0 +1,298 @@
+//! Minimal HCI client used to discover nearby devices on Linux.
+//! Runs a general inquiry on the radio and resolves the names of the devices that answered.
+use crate::error::EdifierError;
+use std::io;
+use std::mem::size_of;
+use std::time::{Duration, Instant};
+
+const BTPROTO_HCI: i32 = 1;
+const SOL_HCI: i32 = 0;
+const HCI_FILTER: i32 = 2;
+const HCI_INQUIRY: libc::c_ulong = 0x800448F0;
+const IREQ_CACHE_FLUSH: u16 = 0x0001;
+const GIAC_LAP: [u8; 3] = [0x33, 0x8B, 0x9E];
+const INQUIRY_MAX_RESPONSES: usize = 32;
+const INQUIRY_MAX_LENGTH: u8 = 48;
+
+const HCI_COMMAND_PKT: u8 = 0x01;
+const HCI_EVENT_PKT: u8 = 0x04;
+const EVT_REMOTE_NAME_REQ_COMPLETE: u8 = 0x07;
+const EVT_CMD_STATUS: u8 = 0x0F;
+const OPCODE_REMOTE_NAME_REQ: u16 = 0x0419;
+
+/// Duration of the inquiry length unit.
+const INQUIRY_UNIT: Duration = Duration::from_millis(1280);
+
+#[repr(C)]
+struct HciInquiryRequest {
+    dev_id: u16,
+    flags: u16,
+    lap: [u8; 3],
+    length: u8,
+    num_rsp: u8,
+}
+
+/// Inquiry response of a single device.
+#[repr(C, packed)]
+#[derive(Clone, Copy, Default)]
+pub(super) struct InquiryInfo {
+    pub(super) bdaddr: [u8; 6],
+    pscan_rep_mode: u8,
+    pscan_period_mode: u8,
+    pscan_mode: u8,
+    dev_class: [u8; 3],
+    clock_offset: u16,
+}
+
+impl InquiryInfo {
+    /// Creates the inquiry info of a device known only by its address, using the default
+    /// page scan parameters. They are not used when the device is already connected.
+    pub(super) fn from_address(bdaddr: [u8; 6]) -> Self {
+        InquiryInfo {
+            bdaddr,
+            pscan_rep_mode: 0x02,
+            ..Default::default()
+        }
+    }
+}
+
+#[repr(C)]
+struct HciInquiryBuffer {
+    request: HciInquiryRequest,
+    info: [InquiryInfo; INQUIRY_MAX_RESPONSES],
+}
+
+#[repr(C)]
+struct SockAddrHci {
+    hci_family: libc::sa_family_t,
+    hci_dev: u16,
+    hci_channel: u16,
+}
+
+#[repr(C)]
+struct HciFilter {
+    type_mask: u32,
+    event_mask: [u32; 2],
+    opcode: u16,
+}
+
+/// Runs a general inquiry on the radio for the specified duration
+/// and returns the devices that responded.
+pub(super) fn inquiry(dev_id: u16, duration: Duration) -> Result<Vec<InquiryInfo>, EdifierError> {
+    let length = duration
+        .as_millis()
+        .div_ceil(INQUIRY_UNIT.as_millis())
+        .clamp(1, INQUIRY_MAX_LENGTH as u128) as u8;
+
+    let fd = open_hci_socket()?;
+    let mut buffer = HciInquiryBuffer {
+        request: HciInquiryRequest {
+            dev_id,
+            flags: IREQ_CACHE_FLUSH,
+            lap: GIAC_LAP,
+            length,
+            num_rsp: INQUIRY_MAX_RESPONSES as u8,
+        },
+        info: [InquiryInfo::default(); INQUIRY_MAX_RESPONSES],
+    };
+
+    let result = unsafe { libc::ioctl(fd, HCI_INQUIRY, &mut buffer) };
+    let error = io::Error::last_os_error();
+    unsafe { libc::close(fd) };
+    if result < 0 {
+        return Err(error.into());
+    }
+
+    let count = (buffer.request.num_rsp as usize).min(INQUIRY_MAX_RESPONSES);
+    Ok(buffer.info[..count].to_vec())
+}
+
+/// Requests the friendly name of the device found by the inquiry.
+pub(super) fn read_remote_name(dev_id: u16, info: &InquiryInfo, timeout: Duration) -> Option<String> {
+    let fd = open_hci_socket().ok()?;
+    let result = request_remote_name(fd, dev_id, info, timeout);
+    unsafe { libc::close(fd) };
+
+    result
+}
+
+fn request_remote_name(
+    fd: i32,
+    dev_id: u16,
+    info: &InquiryInfo,
+    timeout: Duration,
+) -> Option<String> {
+    let address = SockAddrHci {
+        hci_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+        hci_dev: dev_id,
+        hci_channel: 0,
+    };
+    let bind_result = unsafe {
+        libc::bind(
+            fd,
+            &address as *const SockAddrHci as *const libc::sockaddr,
+            size_of::<SockAddrHci>() as libc::socklen_t,
+        )
+    };
+    if bind_result < 0 {
+        return None;
+    }
+
+    let filter = HciFilter {
+        type_mask: 1 << HCI_EVENT_PKT,
+        event_mask: [(1 << EVT_REMOTE_NAME_REQ_COMPLETE) | (1 << EVT_CMD_STATUS), 0],
+        opcode: 0,
+    };
+    let filter_result = unsafe {
+        libc::setsockopt(
+            fd,
+            SOL_HCI,
+            HCI_FILTER,
+            &filter as *const HciFilter as *const libc::c_void,
+            size_of::<HciFilter>() as libc::socklen_t,
+        )
+    };
+    if filter_result < 0 {
+        return None;
+    }
+
+    let command = build_remote_name_request(info);
+    if unsafe { libc::write(fd, command.as_ptr().cast(), command.len()) } < 0 {
+        return None;
+    }
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let mut poll_fd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        if unsafe { libc::poll(&mut poll_fd, 1, remaining.as_millis() as i32) } <= 0 {
+            return None;
+        }
+
+        let mut buffer = [0u8; 260];
+        let bytes_read = unsafe { libc::read(fd, buffer.as_mut_ptr().cast(), buffer.len()) };
+        if bytes_read <= 0 {
+            return None;
+        }
+
+        match parse_name_event(&buffer[..bytes_read as usize], info.bdaddr) {
+            NameEvent::Name(name) => return Some(name),
+            NameEvent::Failed => return None,
+            NameEvent::Other => continue,
+        }
+    }
+}
+
+fn open_hci_socket() -> Result<i32, EdifierError> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_BLUETOOTH,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+            BTPROTO_HCI,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    Ok(fd)
+}
+
+/// Builds the Remote Name Request command packet using the page scan parameters
+/// reported by the inquiry.
+fn build_remote_name_request(info: &InquiryInfo) -> Vec<u8> {
+    let clock_offset = { info.clock_offset } | 0x8000;
+
+    let mut packet = vec![HCI_COMMAND_PKT];
+    packet.extend_from_slice(&OPCODE_REMOTE_NAME_REQ.to_le_bytes());
+    packet.push(10);
+    packet.extend_from_slice(&info.bdaddr);
+    packet.extend_from_slice(&[info.pscan_rep_mode, 0x00]);
+    packet.extend_from_slice(&clock_offset.to_le_bytes());
+    packet
+}
+
+#[derive(Debug, PartialEq)]
+enum NameEvent {
+    Name(String),
+    Failed,
+    Other,
+}
+
+/// Parses the HCI event answering the Remote Name Request for the device.
+fn parse_name_event(packet: &[u8], bdaddr: [u8; 6]) -> NameEvent {
+    match packet {
+        [HCI_EVENT_PKT, EVT_CMD_STATUS, _, status, _, opcode @ ..]
+            if opcode.get(..2) == Some(&OPCODE_REMOTE_NAME_REQ.to_le_bytes()) && *status != 0 =>
+        {
+            NameEvent::Failed
+        }
+        [HCI_EVENT_PKT, EVT_REMOTE_NAME_REQ_COMPLETE, _, status, rest @ ..]
+            if rest.get(..6) == Some(&bdaddr) =>
+        {
+            if *status != 0 {
+                return NameEvent::Failed;
+            }
+            let name = &rest[6..];
+            let length = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+            NameEvent::Name(String::from_utf8_lossy(&name[..length]).to_string())
+        }
+        _ => NameEvent::Other,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BDADDR: [u8; 6] = [0x56, 0x34, 0x12, 0x7C, 0x1E, 0x00];
+
+    #[test]
+    fn test_remote_name_request() {
+        let info = InquiryInfo {
+            bdaddr: BDADDR,
+            pscan_rep_mode: 0x01,
+            clock_offset: 0x1234,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            build_remote_name_request(&info),
+            [
+                0x01, 0x19, 0x04, 0x0A, 0x56, 0x34, 0x12, 0x7C, 0x1E, 0x00, 0x01, 0x00, 0x34, 0x92
+            ]
+        );
+
+        assert_eq!(
+            build_remote_name_request(&InquiryInfo::from_address(BDADDR)),
+            [
+                0x01, 0x19, 0x04, 0x0A, 0x56, 0x34, 0x12, 0x7C, 0x1E, 0x00, 0x02, 0x00, 0x00, 0x80
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_name_event() {
+        let mut event = vec![0x04, 0x07, 0xFF, 0x00];
+        event.extend_from_slice(&BDADDR);
+        event.extend_from_slice(b"EDIFIER W820NB\0\0\0");
+
+        assert_eq!(
+            parse_name_event(&event, BDADDR),
+            NameEvent::Name("EDIFIER W820NB".to_string())
+        );
+        assert_eq!(parse_name_event(&event, [0; 6]), NameEvent::Other);
+        assert_eq!(
+            parse_name_event(&[0x04, 0x0F, 0x04, 0x0C, 0x01, 0x19, 0x04], BDADDR),
+            NameEvent::Failed
+        );
+        assert_eq!(
+            parse_name_event(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x19, 0x04], BDADDR),
+            NameEvent::Other
+        );
+    }
+}
@@ -0,0 +1,224 @@
+//! Minimal SDP client used to resolve the RFCOMM channel of a service on Linux.
+use crate::error::EdifierError;
+use std::io;
+use std::mem::size_of;
+
+const BTPROTO_L2CAP: i32 = 0;
+const SDP_PSM: u16 = 0x0001;
+
+const PDU_SERVICE_SEARCH_ATTRIBUTE_REQUEST: u8 = 0x06;
+const PDU_SERVICE_SEARCH_ATTRIBUTE_RESPONSE: u8 = 0x07;
+const ATTR_PROTOCOL_DESCRIPTOR_LIST: u16 = 0x0004;
+const RFCOMM_UUID: u128 = 0x00000003_0000_1000_8000_00805F9B34FB;
+const BASE_UUID: u128 = 0x00000000_0000_1000_8000_00805F9B34FB;
+
+#[repr(C)]
+struct SockAddrL2 {
+    l2_family: libc::sa_family_t,
+    l2_psm: u16,
+    l2_bdaddr: [u8; 6],
+    l2_cid: u16,
+    l2_bdaddr_type: u8,
+}
+
+/// SDP data element.
+#[derive(Debug, PartialEq)]
+enum DataElement {
+    Uint(u64),
+    Uuid(u128),
+    Sequence(Vec<DataElement>),
+    Other,
+}
+
+/// Queries the SDP server of the device with the given address and returns the RFCOMM channel
+/// of the specified service, or `None` when the device does not provide it.
+pub(super) fn find_rfcomm_channel(
+    bdaddr: [u8; 6],
+    service_uuid: u128,
+) -> Result<Option<u8>, EdifierError> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_BLUETOOTH,
+            libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC,
+            BTPROTO_L2CAP,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error().into());
+    }
+
+    let result = query_protocol_descriptors(fd, bdaddr, service_uuid);
+    unsafe { libc::close(fd) };
+
+    Ok(find_channel(&result?))
+}
+
+fn query_protocol_descriptors(
+    fd: i32,
+    bdaddr: [u8; 6],
+    service_uuid: u128,
+) -> Result<Vec<DataElement>, EdifierError> {
+    let address = SockAddrL2 {
+        l2_family: libc::AF_BLUETOOTH as libc::sa_family_t,
+        l2_psm: SDP_PSM.to_le(),
+        l2_bdaddr: bdaddr,
+        l2_cid: 0,
+        l2_bdaddr_type: 0,
+    };
+
+    let connect_result = unsafe {
+        libc::connect(
+            fd,
+            &address as *const SockAddrL2 as *const libc::sockaddr,
+            size_of::<SockAddrL2>() as libc::socklen_t,
+        )
+    };
+    if connect_result < 0 {
+        return Err(EdifierError::ConnectFailed(io::Error::last_os_error()));
+    }
+
+    let mut attribute_lists = Vec::new();
+    let mut continuation = vec![0u8];
+    let mut transaction_id = 0u16;
+
+    loop {
+        transaction_id = transaction_id.wrapping_add(1);
+        let request = build_request(transaction_id, service_uuid, &continuation);
+        if unsafe { libc::send(fd, request.as_ptr().cast(), request.len(), 0) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        let mut buffer = [0u8; 1024];
+        let bytes_read = unsafe { libc::recv(fd, buffer.as_mut_ptr().cast(), buffer.len(), 0) };
+        if bytes_read < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        continuation = parse_response(&buffer[..bytes_read as usize], &mut attribute_lists)?;
+        if continuation == [0] {
+            break;
+        }
+    }
+
+    match parse_data_element(&attribute_lists) {
+        Some((element, _)) => Ok(vec![element]),
+        None => Err(malformed_response()),
+    }
+}
+
+/// Builds a ServiceSearchAttributeRequest PDU asking for the protocol descriptor list
+/// of the service with the given UUID.
+fn build_request(transaction_id: u16, service_uuid: u128, continuation: &[u8]) -> Vec<u8> {
+    let mut parameters = vec![0x35, 0x11, 0x1C];
+    parameters.extend_from_slice(&service_uuid.to_be_bytes());
+    parameters.extend_from_slice(&u16::MAX.to_be_bytes());
+    parameters.extend_from_slice(&[0x35, 0x03, 0x09]);
+    parameters.extend_from_slice(&ATTR_PROTOCOL_DESCRIPTOR_LIST.to_be_bytes());
+    parameters.extend_from_slice(continuation);
+
+    let mut pdu = vec![PDU_SERVICE_SEARCH_ATTRIBUTE_REQUEST];
+    pdu.extend_from_slice(&transaction_id.to_be_bytes());
+    pdu.extend_from_slice(&(parameters.len() as u16).to_be_bytes());
+    pdu.extend_from_slice(&parameters);
+    pdu
+}
+
+/// Appends the attribute list bytes of the response to `attribute_lists`
+/// and returns the continuation state.
+fn parse_response(data: &[u8], attribute_lists: &mut Vec<u8>) -> Result<Vec<u8>, EdifierError> {
+    if data.len() < 7 || data[0] != PDU_SERVICE_SEARCH_ATTRIBUTE_RESPONSE {
+        return Err(malformed_response());
+    }
+
+    let byte_count = u16::from_be_bytes([data[5], data[6]]) as usize;
+    let continuation_start = 7 + byte_count;
+    if data.len() <= continuation_start {
+        return Err(malformed_response());
+    }
+
+    attribute_lists.extend_from_slice(&data[7..continuation_start]);
+    Ok(data[continuation_start..].to_vec())
+}
+
+/// Parses a single data element and returns it along with the number of bytes consumed.
+fn parse_data_element(data: &[u8]) -> Option<(DataElement, usize)> {
+    let descriptor = *data.first()?;
+    let element_type = descriptor >> 3;
+    let size_index = descriptor & 0x07;
+
+    let (size, header_size) = match size_index {
+        0..=4 if element_type == 0 => (0, 1),
+        0..=4 => (1usize << size_index, 1),
+        5 => (*data.get(1)? as usize, 2),
+        6 => (
+            u16::from_be_bytes([*data.get(1)?, *data.get(2)?]) as usize,
+            3,
+        ),
+        _ => (
+            u32::from_be_bytes([*data.get(1)?, *data.get(2)?, *data.get(3)?, *data.get(4)?])
+                as usize,
+            5,
+        ),
+    };
+
+    let value = data.get(header_size..header_size + size)?;
+    let element = match element_type {
+        1 => DataElement::Uint(value.iter().fold(0, |acc, &b| (acc << 8) | b as u64)),
+        3 => DataElement::Uuid(match size {
+            16 => u128::from_be_bytes(value.try_into().ok()?),
+            _ => (value.iter().fold(0, |acc, &b| (acc << 8) | b as u128) << 96) | BASE_UUID,
+        }),
+        6 | 7 => {
+            let mut elements = Vec::new();
+            let mut offset = 0;
+            while offset < value.len() {
+                let (element, consumed) = parse_data_element(&value[offset..])?;
+                elements.push(element);
+                offset += consumed;
+            }
+            DataElement::Sequence(elements)
+        }
+        _ => DataElement::Other,
+    };
+
+    Some((element, header_size + size))
+}
+
+fn malformed_response() -> EdifierError {
+    io::Error::new(io::ErrorKind::InvalidData, "malformed SDP response").into()
+}
+
+/// Searches the elements for an RFCOMM protocol descriptor and returns its channel.
+fn find_channel(elements: &[DataElement]) -> Option<u8> {
+    if let [
+        DataElement::Uuid(RFCOMM_UUID),
+        DataElement::Uint(channel),
+        ..,
+    ] = elements
+    {
+        return Some(*channel as u8);
+    }
+
+    elements.iter().find_map(|element| match element {
+        DataElement::Sequence(children) => find_channel(children),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_channel() {
+        /* Attribute lists of one record: ProtocolDescriptorList = ((L2CAP), (RFCOMM, 5)) */
+        let data = [
+            0x35, 0x13, 0x35, 0x11, 0x09, 0x00, 0x04, 0x35, 0x0C, 0x35, 0x03, 0x19, 0x01, 0x00,
+            0x35, 0x05, 0x19, 0x00, 0x03, 0x08, 0x05,
+        ];
+        let (element, consumed) = parse_data_element(&data).unwrap();
+
+        assert_eq!(consumed, data.len());
+        assert_eq!(find_channel(&[element]), Some(5));
+    }
+}
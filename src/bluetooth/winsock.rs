@@ -0,0 +1,503 @@
+﻿//! WinSock RFCOMM backend built on the Windows Bluetooth API.
+use super::{format_address, is_edifier_name, select_device, DeviceInfo, DeviceSelector};
+use crate::decoder::FrameDecoder;
+use crate::error::EdifierError;
+use crate::message::EdifierMessage;
+use crate::transport::Transport;
+#[cfg(feature = "debug")]
+use crate::utils::join_hex;
+use std::io;
+use std::mem::zeroed;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use windows::Win32::Devices::Bluetooth::{
+    BluetoothEnumerateInstalledServices, BluetoothFindDeviceClose, BluetoothFindFirstDevice, BluetoothFindFirstRadio,
+    BluetoothFindNextDevice, BluetoothFindNextRadio, BluetoothFindRadioClose, BluetoothSetServiceState,
+    AF_BTH, BLUETOOTH_DEVICE_INFO, BLUETOOTH_DEVICE_SEARCH_PARAMS,
+    BLUETOOTH_FIND_RADIO_PARAMS, BLUETOOTH_SERVICE_DISABLE, BLUETOOTH_SERVICE_ENABLE,
+    BTHPROTO_RFCOMM, BTH_ERROR_SUCCESS, SOCKADDR_BTH,
+};
+use windows::Win32::Foundation::{HANDLE, SYSTEMTIME};
+use windows::Win32::Networking::WinSock;
+use windows::Win32::Networking::WinSock::{
+    WSACleanup, WSAGetLastError, WSAStartup, FIONREAD, INVALID_SOCKET, SEND_RECV_FLAGS, SOCKADDR,
+    SOCKET, SOCKET_ERROR, SOCK_STREAM, SOL_SOCKET, SO_RCVTIMEO, SO_SNDTIMEO, WSADATA,
+    WSAETIMEDOUT,
+};
+use windows_core::GUID;
+
+type BluetoothDevice = (HANDLE, BLUETOOTH_DEVICE_INFO);
+
+const WINSOCK_VERSION_2_2: u16 = 0x0202;
+const A2DP_SINK_UUID: GUID = GUID::from_u128(0x0000110B_0000_1000_8000_00805F9B34FB);
+const HFP_AG_UUID: GUID = GUID::from_u128(0x0000111E_0000_1000_8000_00805F9B34FB);
+const AUDIO_SERVICE_UUIDS: [GUID; 2] = [A2DP_SINK_UUID, HFP_AG_UUID];
+const INQUIRY_UNIT: Duration = Duration::from_millis(1280);
+const INQUIRY_MAX_LENGTH: u8 = 48;
+
+/// Resets Bluetooth audio-related services for the device that provides the specified SPP service.
+pub(crate) fn pair(spp_uuid: u128, selector: Option<&DeviceSelector>) -> Result<(), EdifierError> {
+    let (radio_handle, device_info) = find_device(&GUID::from_u128(spp_uuid), selector)?;
+
+    for service_guid in AUDIO_SERVICE_UUIDS {
+        reset_bluetooth_service(radio_handle, &device_info, &service_guid)?;
+    }
+
+    Ok(())
+}
+
+/// Lists the devices on all radios that provide the specified SPP service.
+pub(crate) fn list_devices(spp_uuid: u128) -> Result<Vec<DeviceInfo>, EdifierError> {
+    let devices = find_devices(&GUID::from_u128(spp_uuid))?;
+
+    Ok(devices.iter().map(|(_, info)| to_device_info(info)).collect())
+}
+
+/// Runs an inquiry on every radio and returns the nearby devices that look like Edifier
+/// headsets: named by the vendor, or providing the specified SPP service.
+pub(crate) fn scan_devices(
+    spp_uuid: u128,
+    timeout: Duration,
+) -> Result<Vec<DeviceInfo>, EdifierError> {
+    let service_guid = GUID::from_u128(spp_uuid);
+    let length = timeout
+        .as_millis()
+        .div_ceil(INQUIRY_UNIT.as_millis())
+        .clamp(1, INQUIRY_MAX_LENGTH as u128) as u8;
+
+    let devices = enumerate_devices(Some(length), |radio_handle, device_info| {
+        is_edifier_name(&device_name(device_info))
+            || device_has_service(radio_handle, device_info, &service_guid)
+    })?;
+
+    Ok(devices.iter().map(|(_, info)| to_device_info(info)).collect())
+}
+
+/// RFCOMM Bluetooth socket connection to the device SPP service.
+#[derive(Debug)]
+pub struct BluetoothTransport {
+    socket: SOCKET,
+    bt_address: u64,
+    service_guid: GUID,
+    decoder: FrameDecoder,
+}
+
+impl BluetoothTransport {
+    /// Opens an RFCOMM Bluetooth socket connection to the device that provides the specified SPP service.
+    pub fn connect(spp_uuid: u128) -> Result<Self, EdifierError> {
+        Self::open(spp_uuid, None)
+    }
+
+    /// Opens an RFCOMM Bluetooth socket connection to the device matching the selector
+    /// among the devices that provide the specified SPP service.
+    pub fn connect_to(spp_uuid: u128, selector: &DeviceSelector) -> Result<Self, EdifierError> {
+        Self::open(spp_uuid, Some(selector))
+    }
+
+    fn open(spp_uuid: u128, selector: Option<&DeviceSelector>) -> Result<Self, EdifierError> {
+        let spp_guid = &GUID::from_u128(spp_uuid);
+
+        startup_winsock()?;
+
+        let result = find_device(spp_guid, selector).and_then(|(_radio, device_info)| {
+            let bt_address = unsafe { device_info.Address.Anonymous.ullLong };
+            Ok((open_socket(bt_address, spp_guid)?, bt_address))
+        });
+
+        match result {
+            Ok((socket, bt_address)) => Ok(Self {
+                socket,
+                bt_address,
+                service_guid: *spp_guid,
+                decoder: FrameDecoder::new(),
+            }),
+            Err(e) => {
+                unsafe { WSACleanup() };
+                Err(e)
+            }
+        }
+    }
+}
+
+impl Transport for BluetoothTransport {
+    /// Sends raw frame bytes over the Bluetooth socket, until the whole frame is written.
+    fn send(&mut self, message: &EdifierMessage, timeout: Duration) -> Result<(), EdifierError> {
+        #[cfg(feature = "debug")]
+        println!("BTQ: [{}]", join_hex(message.as_slice(), ", "));
+
+        let deadline = Instant::now() + timeout;
+        let mut data = message.as_slice();
+        while !data.is_empty() {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(EdifierError::Timeout);
+            }
+            set_socket_timeout(self.socket, SO_SNDTIMEO, remaining)?;
+
+            let bytes_sent = unsafe { WinSock::send(self.socket, data, SEND_RECV_FLAGS(0)) };
+            if bytes_sent == SOCKET_ERROR {
+                return Err(last_socket_error().into());
+            }
+            if bytes_sent == 0 {
+                return Err(EdifierError::Disconnected);
+            }
+
+            data = &data[bytes_sent as usize..];
+        }
+
+        Ok(())
+    }
+
+    /// Reads from the Bluetooth socket until a complete frame is reassembled.
+    fn receive(&mut self, timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if let Some(message) = self.decoder.next_message() {
+                return Ok(message);
+            }
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                /* Only take the bytes already received. */
+                if available_bytes(self.socket)? == 0 {
+                    return Err(EdifierError::Timeout);
+                }
+            } else {
+                set_socket_timeout(self.socket, SO_RCVTIMEO, remaining)?;
+            }
+
+            let mut buffer = [0u8; 256];
+            let bytes_read = unsafe { WinSock::recv(self.socket, &mut buffer, SEND_RECV_FLAGS(0)) };
+            if bytes_read == SOCKET_ERROR {
+                return Err(last_socket_error().into());
+            }
+            if bytes_read == 0 {
+                return Err(EdifierError::Disconnected);
+            }
+
+            let chunk = &buffer[..bytes_read as usize];
+
+            #[cfg(feature = "debug")]
+            println!("BTR: [{}]", join_hex(chunk, ", "));
+
+            self.decoder.push(chunk);
+        }
+    }
+
+    /// Closes the Bluetooth socket and cleans up the WinSock session.
+    fn close(&mut self) {
+        if self.socket == INVALID_SOCKET {
+            return;
+        }
+
+        unsafe {
+            WinSock::closesocket(self.socket);
+            WSACleanup();
+        }
+        self.socket = INVALID_SOCKET;
+    }
+
+    /// Closes the Bluetooth socket and connects again to the same device and service.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        self.close();
+        startup_winsock()?;
+
+        match open_socket(self.bt_address, &self.service_guid) {
+            Ok(socket) => self.socket = socket,
+            Err(e) => {
+                unsafe { WSACleanup() };
+                return Err(e);
+            }
+        }
+        self.decoder = FrameDecoder::new();
+
+        Ok(())
+    }
+}
+
+impl Drop for BluetoothTransport {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+/// Connects an RFCOMM socket to the device service.
+fn open_socket(bt_address: u64, service_guid: &GUID) -> Result<SOCKET, EdifierError> {
+    let address = bluetooth_socket_address(bt_address, service_guid);
+
+    unsafe {
+        let socket = WinSock::socket(AF_BTH as i32, SOCK_STREAM, BTHPROTO_RFCOMM as i32)?;
+        if socket == INVALID_SOCKET {
+            return Err(last_socket_error().into());
+        }
+
+        let connect_result = WinSock::connect(
+            socket,
+            &address as *const SOCKADDR_BTH as *const SOCKADDR,
+            size_of::<SOCKADDR_BTH>() as i32,
+        );
+
+        if connect_result == SOCKET_ERROR {
+            let error = WSAGetLastError();
+            WinSock::closesocket(socket);
+            return if error == WSAETIMEDOUT {
+                Err(EdifierError::Timeout)
+            } else {
+                Err(EdifierError::ConnectFailed(io::Error::from_raw_os_error(error.0)))
+            };
+        }
+
+        Ok(socket)
+    }
+}
+
+/// Sets the socket send or receive timeout.
+fn set_socket_timeout(socket: SOCKET, option: i32, timeout: Duration) -> Result<(), EdifierError> {
+    /* Zero value means no timeout at all. */
+    let millis = (timeout.as_millis() as u32).max(1);
+
+    let result =
+        unsafe { WinSock::setsockopt(socket, SOL_SOCKET, option, Some(&millis.to_ne_bytes())) };
+    if result == SOCKET_ERROR {
+        return Err(last_socket_error().into());
+    }
+
+    Ok(())
+}
+
+/// Returns the number of received bytes waiting to be read.
+fn available_bytes(socket: SOCKET) -> Result<u32, EdifierError> {
+    let mut available = 0u32;
+    let result = unsafe { WinSock::ioctlsocket(socket, FIONREAD, &mut available) };
+    if result == SOCKET_ERROR {
+        return Err(last_socket_error().into());
+    }
+
+    Ok(available)
+}
+
+/// Returns the last WinSock error of the calling thread.
+fn last_socket_error() -> io::Error {
+    io::Error::from_raw_os_error(unsafe { WSAGetLastError() }.0)
+}
+
+fn startup_winsock() -> Result<(), EdifierError> {
+    unsafe {
+        let mut data: WSADATA = zeroed();
+        let result = WSAStartup(WINSOCK_VERSION_2_2, &mut data);
+        if result != 0 {
+            return Err(io::Error::from_raw_os_error(result).into());
+        }
+    }
+    Ok(())
+}
+
+fn reset_bluetooth_service(
+    radio_handle: HANDLE,
+    device_info: &BLUETOOTH_DEVICE_INFO,
+    service_guid: &GUID,
+) -> Result<(), EdifierError> {
+    unsafe {
+        BluetoothSetServiceState(
+            Some(radio_handle),
+            device_info,
+            service_guid,
+            BLUETOOTH_SERVICE_DISABLE,
+        );
+
+        let result = BluetoothSetServiceState(
+            Some(radio_handle),
+            device_info,
+            service_guid,
+            BLUETOOTH_SERVICE_ENABLE,
+        );
+
+        if result != BTH_ERROR_SUCCESS {
+            return Err(io::Error::from_raw_os_error(result as i32).into());
+        }
+    }
+
+    Ok(())
+}
+
+fn bluetooth_socket_address(bt_address: u64, service_guid: &GUID) -> SOCKADDR_BTH {
+    let mut address: SOCKADDR_BTH = unsafe { zeroed() };
+    address.addressFamily = AF_BTH;
+    address.btAddr = bt_address;
+    address.serviceClassId = *service_guid;
+    address
+}
+
+/// Checks if the device has the specified service enabled.
+fn device_has_service(
+    radio_handle: HANDLE,
+    device_info: &BLUETOOTH_DEVICE_INFO,
+    service_guid: &GUID,
+) -> bool {
+    let mut service_guids = [GUID::default(); 10];
+    let mut service_guid_count = service_guids.len() as u32;
+
+    let result = unsafe {
+        BluetoothEnumerateInstalledServices(
+            radio_handle.into(),
+            device_info,
+            &mut service_guid_count,
+            service_guids.as_mut_ptr().into(),
+        )
+    };
+
+    result == BTH_ERROR_SUCCESS
+        && service_guids[..service_guid_count as usize]
+            .iter()
+            .any(|installed_service_guid| installed_service_guid == service_guid)
+}
+
+fn to_device_info(device_info: &BLUETOOTH_DEVICE_INFO) -> DeviceInfo {
+    DeviceInfo {
+        address: device_address(device_info),
+        name: Some(device_name(device_info)),
+        connected: device_info.fConnected.as_bool(),
+        remembered: device_info.fRemembered.as_bool(),
+        authenticated: device_info.fAuthenticated.as_bool(),
+        last_seen: system_time(&device_info.stLastSeen),
+        last_used: system_time(&device_info.stLastUsed),
+    }
+}
+
+/// Returns the device address in display order.
+fn device_address(device_info: &BLUETOOTH_DEVICE_INFO) -> [u8; 6] {
+    let mut address = unsafe { device_info.Address.Anonymous.rgBytes };
+    address.reverse();
+    address
+}
+
+/// Returns the device friendly name.
+fn device_name(device_info: &BLUETOOTH_DEVICE_INFO) -> String {
+    let name = &device_info.szName;
+    let length = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    String::from_utf16_lossy(&name[..length])
+}
+
+/// Converts the UTC system time, zero time meaning never, into the timestamp.
+fn system_time(time: &SYSTEMTIME) -> Option<SystemTime> {
+    if time.wYear == 0 {
+        return None;
+    }
+
+    /* Days since the Unix epoch for the proleptic Gregorian calendar date. */
+    let year = time.wYear as i64 - (time.wMonth <= 2) as i64;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((time.wMonth as i64 + 9) % 12) + 2) / 5 + time.wDay as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400
+        + time.wHour as i64 * 3600
+        + time.wMinute as i64 * 60
+        + time.wSecond as i64;
+    let millis = u64::try_from(seconds).ok()? * 1000 + time.wMilliseconds as u64;
+
+    Some(UNIX_EPOCH + Duration::from_millis(millis))
+}
+
+/// Searches for the Bluetooth device that provides the service matching the specified UUID.
+/// Without a selector the first device found is returned.
+fn find_device(
+    service_guid: &GUID,
+    selector: Option<&DeviceSelector>,
+) -> Result<BluetoothDevice, EdifierError> {
+    let devices = find_devices(service_guid)?;
+
+    let Some(selector) = selector else {
+        return devices
+            .into_iter()
+            .next()
+            .ok_or(EdifierError::DeviceNotFound);
+    };
+
+    let devices = devices
+        .into_iter()
+        .filter(|(_, device_info)| {
+            selector.matches(device_address(device_info), Some(&device_name(device_info)))
+        })
+        .collect();
+
+    select_device(devices, selector, |(_, device_info)| {
+        format!(
+            "{} ({})",
+            device_name(device_info),
+            format_address(device_address(device_info))
+        )
+    })
+}
+
+/// Searches all radios for the Bluetooth devices that provide the service matching the specified UUID.
+fn find_devices(service_guid: &GUID) -> Result<Vec<BluetoothDevice>, EdifierError> {
+    enumerate_devices(None, |radio_handle, device_info| {
+        device_has_service(radio_handle, device_info, service_guid)
+    })
+}
+
+/// Enumerates the devices known to all radios, optionally issuing an inquiry of the specified
+/// length in 1.28 s units first, and returns the ones accepted by the filter.
+fn enumerate_devices(
+    inquiry_length: Option<u8>,
+    mut filter: impl FnMut(HANDLE, &BLUETOOTH_DEVICE_INFO) -> bool,
+) -> Result<Vec<BluetoothDevice>, EdifierError> {
+    let find_radio_params = BLUETOOTH_FIND_RADIO_PARAMS {
+        dwSize: size_of::<BLUETOOTH_FIND_RADIO_PARAMS>() as u32,
+    };
+    let mut radio_handle = HANDLE::default();
+    let mut devices = Vec::new();
+
+    unsafe {
+        let find_radio_handle = BluetoothFindFirstRadio(&find_radio_params, &mut radio_handle)
+            .map_err(|_| EdifierError::NoRadio)?;
+
+        let mut device_search_params = BLUETOOTH_DEVICE_SEARCH_PARAMS {
+            dwSize: size_of::<BLUETOOTH_DEVICE_SEARCH_PARAMS>() as u32,
+            fReturnAuthenticated: true.into(),
+            fReturnRemembered: true.into(),
+            fReturnUnknown: true.into(),
+            fReturnConnected: true.into(),
+            fIssueInquiry: inquiry_length.is_some().into(),
+            cTimeoutMultiplier: inquiry_length.unwrap_or(2),
+            hRadio: radio_handle,
+        };
+
+        'radios: loop {
+            device_search_params.hRadio = radio_handle;
+
+            let mut device_info = BLUETOOTH_DEVICE_INFO {
+                dwSize: size_of::<BLUETOOTH_DEVICE_INFO>() as u32,
+                ..Default::default()
+            };
+
+            /* Fails when the radio knows no devices. */
+            if let Ok(find_device_handle) =
+                BluetoothFindFirstDevice(&device_search_params, &mut device_info)
+            {
+                'devices: loop {
+                    if filter(radio_handle, &device_info) {
+                        devices.push((radio_handle, device_info));
+                    }
+
+                    if BluetoothFindNextDevice(find_device_handle, &mut device_info).is_err() {
+                        break 'devices;
+                    }
+                }
+
+                BluetoothFindDeviceClose(find_device_handle)?;
+            }
+
+            if BluetoothFindNextRadio(find_radio_handle, &mut radio_handle).is_err() {
+                break 'radios;
+            }
+        }
+
+        BluetoothFindRadioClose(find_radio_handle)?;
+    }
+
+    Ok(devices)
+}
@@ -0,0 +1,144 @@
+//! Decoding of Edifier frames found in traffic captures, printed as a timeline by the
+//! capture subcommands. Every capture format extracts the exchanged bytes with their
+//! direction, the frames are cut out and named here.
+use crate::raw::format_hex_bytes;
+use edifier::message::{SIGNATURE_ACKNOWLEDGE, SIGNATURE_REQUEST, SIGNATURE_RESPONSE};
+use edifier::{command_name, EdifierError, EdifierMessage, FrameDecoder};
+use std::fmt::Write;
+use std::iter;
+
+pub(crate) mod btsnoop;
+pub(crate) mod logcat;
+
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+
+/// Direction of the captured bytes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub(crate) enum Direction {
+    /// Sent by the app to the device.
+    Sent,
+    /// Received by the app from the device.
+    Received,
+}
+
+/// Bytes exchanged with the device at the capture time.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) struct CapturedData {
+    pub(crate) time: String,
+    pub(crate) direction: Direction,
+    pub(crate) bytes: Vec<u8>,
+}
+
+/// Splits the bytes into frames. Bytes that do not form a valid frame are returned with
+/// the decoding error, a truncated frame ends the data.
+pub(crate) fn split_frames(bytes: &[u8]) -> Vec<Result<EdifierMessage, (Vec<u8>, EdifierError)>> {
+    let mut decoder = FrameDecoder::new();
+    decoder.push(bytes);
+
+    let mut frames: Vec<_> = iter::from_fn(|| decoder.next_frame()).collect();
+    let remaining = decoder.take_remaining();
+    if !remaining.is_empty() {
+        frames.push(Err(remaining));
+    }
+
+    frames
+        .into_iter()
+        .map(|frame| {
+            frame.map_err(|bytes| {
+                let e = EdifierMessage::try_from(bytes.clone())
+                    .err()
+                    .unwrap_or_else(|| EdifierError::MalformedFrame("not a frame".to_string()));
+                (bytes, e)
+            })
+        })
+        .collect()
+}
+
+/// Formats the timeline lines of the frames found in the captured data. Unknown commands
+/// and malformed frames are highlighted when `highlight` is set, and marked in any case.
+pub(crate) fn format_timeline(data: &CapturedData, highlight: bool) -> String {
+    let arrow = match data.direction {
+        Direction::Sent => "->",
+        Direction::Received => "<-",
+    };
+
+    let mut text = String::new();
+    for frame in split_frames(&data.bytes) {
+        let (line, known) = match frame {
+            Ok(message) => {
+                let code = message.command_code();
+                let name = command_name(code);
+                let payload = message.payload().unwrap_or_default();
+                (
+                    format!(
+                        "{} {code:02X} {} [{}]",
+                        signature_name(message.signature()),
+                        name.unwrap_or("UNKNOWN"),
+                        format_hex_bytes(&payload, " ")
+                    ),
+                    name.is_some(),
+                )
+            }
+            Err((bytes, e)) => {
+                let line = format!("malformed [{}]: {e}", format_hex_bytes(&bytes, " "));
+                (line, false)
+            }
+        };
+
+        let line = format!("{} {arrow} {line}", data.time);
+        if highlight && !known {
+            writeln!(text, "{HIGHLIGHT}{line}{RESET}").unwrap();
+        } else {
+            writeln!(text, "{line}").unwrap();
+        }
+    }
+
+    text
+}
+
+fn signature_name(signature: u8) -> &'static str {
+    match signature {
+        SIGNATURE_REQUEST => "request",
+        SIGNATURE_RESPONSE => "response",
+        SIGNATURE_ACKNOWLEDGE => "ack",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_split_frames() {
+        let frames = split_frames(&[
+            0x00, 0xBB, 0x02, 0xC3, 0x0D, 0x21, 0xA6, 0xAA, 0x01, 0xC9, 0x21, 0x8E, 0xBB, 0x02,
+        ]);
+
+        assert_eq!(frames.len(), 4);
+        assert!(matches!(&frames[0], Err((bytes, _)) if bytes == &[0x00]));
+        assert_eq!(frames[1].as_ref().unwrap().command_code(), 0xC3);
+        assert!(matches!(&frames[2], Err((_, EdifierError::CrcMismatch { .. }))));
+        assert!(matches!(&frames[3], Err((bytes, _)) if bytes == &[0xBB, 0x02]));
+    }
+
+    #[test]
+    fn test_format_timeline() {
+        let data = CapturedData {
+            time: "05:25:15.123".to_string(),
+            direction: Direction::Received,
+            bytes: vec![
+                0xBB, 0x02, 0xC3, 0x0D, 0x21, 0xA6, 0xBB, 0x02, 0x68, 0x00, 0x21, 0x3E,
+            ],
+        };
+
+        assert_eq!(
+            format_timeline(&data, false),
+            "05:25:15.123 <- response C3 Get playback status [0D]\n\
+            05:25:15.123 <- response 68 UNKNOWN [00]\n"
+        );
+        assert!(format_timeline(&data, true)
+            .ends_with("\x1b[1;33m05:25:15.123 <- response 68 UNKNOWN [00]\x1b[0m\n"));
+    }
+}
@@ -0,0 +1,380 @@
+//! Parser of btsnoop HCI captures, such as the Android `btsnoop_hci.log`.
+//! ACL packets are reassembled into L2CAP frames, the RFCOMM channels are learned from the
+//! L2CAP connection signaling and the data of their UIH frames is reassembled into frames.
+use super::{split_frames, CapturedData, Direction};
+use crate::datetime::format_time;
+use edifier::{EdifierError, FrameDecoder};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::{Duration, UNIX_EPOCH};
+
+const MAGIC: &[u8] = b"btsnoop\0";
+const FILE_HEADER_SIZE: usize = 16;
+const RECORD_HEADER_SIZE: usize = 24;
+const DATALINK_HCI: u32 = 1001;
+const DATALINK_H4: u32 = 1002;
+/// Microseconds between the btsnoop epoch (0001-01-01) and the Unix epoch.
+const EPOCH_OFFSET: i64 = 0x00DC_DDB3_0F2F_8000;
+
+const FLAG_RECEIVED: u32 = 0x01;
+const FLAG_COMMAND_OR_EVENT: u32 = 0x02;
+const H4_ACL_DATA: u8 = 0x02;
+const ACL_CONTINUATION: u16 = 0x01;
+
+const CID_SIGNALING: u16 = 0x0001;
+const PSM_RFCOMM: u16 = 0x0003;
+const L2CAP_CONNECTION_REQUEST: u8 = 0x02;
+const L2CAP_CONNECTION_RESPONSE: u8 = 0x03;
+
+const RFCOMM_UIH: u8 = 0xEF;
+const RFCOMM_POLL_FINAL: u8 = 0x10;
+
+/// Extracts the data exchanged over RFCOMM. Only the given server channel is kept,
+/// without one the channels carrying valid Edifier frames are detected.
+pub(crate) fn parse_btsnoop(
+    data: &[u8],
+    channel: Option<u8>,
+) -> Result<Vec<CapturedData>, EdifierError> {
+    if data.len() < FILE_HEADER_SIZE || !data.starts_with(MAGIC) {
+        return Err(EdifierError::InvalidValue("Not a btsnoop file.".to_string()));
+    }
+    let datalink = read_u32(data, 12);
+    if datalink != DATALINK_HCI && datalink != DATALINK_H4 {
+        return Err(EdifierError::InvalidValue(format!(
+            "Unsupported btsnoop datalink type: {datalink}."
+        )));
+    }
+
+    let mut parser = Parser::default();
+    let mut offset = FILE_HEADER_SIZE;
+    while offset + RECORD_HEADER_SIZE <= data.len() {
+        let included_length = read_u32(data, offset + 4) as usize;
+        let flags = read_u32(data, offset + 8);
+        let timestamp = i64::from_be_bytes(data[offset + 16..offset + 24].try_into().unwrap());
+
+        let start = offset + RECORD_HEADER_SIZE;
+        let Some(packet) = data.get(start..start + included_length) else {
+            break;
+        };
+        offset = start + included_length;
+
+        let acl = match datalink {
+            DATALINK_H4 => packet.strip_prefix(&[H4_ACL_DATA]),
+            _ => (flags & FLAG_COMMAND_OR_EVENT == 0).then_some(packet),
+        };
+        if let Some(acl) = acl {
+            parser.time = record_time(timestamp);
+            parser.acl_packet(acl, flags & FLAG_RECEIVED != 0);
+        }
+    }
+    parser.finish();
+
+    let channels: HashSet<_> = match channel {
+        Some(channel) => parser
+            .captured
+            .iter()
+            .map(|c| c.0)
+            .filter(|(_, dlci)| dlci >> 1 == channel)
+            .collect(),
+        None => parser
+            .captured
+            .iter()
+            .filter(|(_, data)| split_frames(&data.bytes).first().is_some_and(|f| f.is_ok()))
+            .map(|c| c.0)
+            .collect(),
+    };
+
+    Ok(parser
+        .captured
+        .into_iter()
+        .filter(|(key, _)| channels.contains(key))
+        .map(|(_, data)| data)
+        .collect())
+}
+
+/// Reassembly state of the ACL connections.
+#[derive(Default)]
+struct Parser {
+    time: String,
+    /// Partial L2CAP frames by connection handle and direction.
+    fragments: HashMap<(u16, bool), Vec<u8>>,
+    /// Pending RFCOMM connection requests by connection handle and signaling identifier.
+    requests: HashSet<(u16, u8)>,
+    /// L2CAP channels carrying RFCOMM by connection handle.
+    rfcomm_channels: HashSet<(u16, u16)>,
+    /// Frame reassembly of the RFCOMM DLCIs by connection handle and direction.
+    streams: BTreeMap<(u16, u8, bool), Stream>,
+    /// Frames of the RFCOMM DLCIs by connection handle.
+    captured: Vec<((u16, u8), CapturedData)>,
+}
+
+/// Data of one direction of an RFCOMM DLCI, a frame may span several UIH frames.
+#[derive(Default)]
+struct Stream {
+    decoder: FrameDecoder,
+    /// Time of the last UIH frame.
+    time: String,
+}
+
+impl Parser {
+    fn acl_packet(&mut self, packet: &[u8], received: bool) {
+        if packet.len() < 4 {
+            return;
+        }
+        let header = read_u16_le(packet, 0);
+        let handle = header & 0x0FFF;
+        let length = read_u16_le(packet, 2) as usize;
+        let data = &packet[4..(4 + length).min(packet.len())];
+
+        let buffer = self.fragments.entry((handle, received)).or_default();
+        if (header >> 12) & 0x03 != ACL_CONTINUATION {
+            buffer.clear();
+        }
+        buffer.extend_from_slice(data);
+
+        if buffer.len() < 4 {
+            return;
+        }
+        let frame_size = read_u16_le(buffer, 0) as usize + 4;
+        if buffer.len() < frame_size {
+            return;
+        }
+
+        let mut frame = std::mem::take(buffer);
+        frame.truncate(frame_size);
+        self.l2cap_frame(handle, received, &frame);
+    }
+
+    fn l2cap_frame(&mut self, handle: u16, received: bool, frame: &[u8]) {
+        let cid = read_u16_le(frame, 2);
+        let payload = &frame[4..];
+
+        if cid == CID_SIGNALING {
+            self.signaling(handle, payload);
+        } else if self.rfcomm_channels.contains(&(handle, cid)) {
+            self.rfcomm_frame(handle, received, payload);
+        }
+    }
+
+    /// Learns the L2CAP channels of the accepted RFCOMM connections.
+    fn signaling(&mut self, handle: u16, mut payload: &[u8]) {
+        while payload.len() >= 4 {
+            let code = payload[0];
+            let identifier = payload[1];
+            let length = read_u16_le(payload, 2) as usize;
+            let Some(data) = payload.get(4..4 + length) else {
+                return;
+            };
+            payload = &payload[4 + length..];
+
+            match code {
+                L2CAP_CONNECTION_REQUEST
+                    if data.len() >= 4 && read_u16_le(data, 0) == PSM_RFCOMM =>
+                {
+                    self.requests.insert((handle, identifier));
+                }
+                L2CAP_CONNECTION_RESPONSE if data.len() >= 6 => {
+                    let accepted = read_u16_le(data, 4) == 0;
+                    if accepted && self.requests.remove(&(handle, identifier)) {
+                        self.rfcomm_channels.insert((handle, read_u16_le(data, 0)));
+                        self.rfcomm_channels.insert((handle, read_u16_le(data, 2)));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Extracts the information field of UIH frames on data channels.
+    fn rfcomm_frame(&mut self, handle: u16, received: bool, frame: &[u8]) {
+        let [address, control, length, rest @ ..] = frame else {
+            return;
+        };
+        let dlci = address >> 2;
+        if dlci == 0 || control & !RFCOMM_POLL_FINAL != RFCOMM_UIH {
+            return;
+        }
+
+        let (length, mut data) = if length & 0x01 != 0 {
+            ((length >> 1) as usize, rest)
+        } else {
+            let Some((high, rest)) = rest.split_first() else {
+                return;
+            };
+            ((length >> 1) as usize | (*high as usize) << 7, rest)
+        };
+        if control & RFCOMM_POLL_FINAL != 0 {
+            /* Credit based flow control byte. */
+            data = data.get(1..).unwrap_or_default();
+        }
+
+        let information = &data[..length.min(data.len())];
+        if information.is_empty() {
+            return;
+        }
+
+        let stream = self.streams.entry((handle, dlci, received)).or_default();
+        stream.decoder.push(information);
+        stream.time.clone_from(&self.time);
+        while let Some(frame) = stream.decoder.next_frame() {
+            let bytes = frame.map_or_else(|bytes| bytes, |message| message.as_slice().to_vec());
+            self.captured.push((
+                (handle, dlci),
+                captured_data(&stream.time, received, bytes),
+            ));
+        }
+    }
+
+    /// Adds the frames truncated at the end of the capture.
+    fn finish(&mut self) {
+        for (&(handle, dlci, received), stream) in &mut self.streams {
+            let bytes = stream.decoder.take_remaining();
+            if !bytes.is_empty() {
+                self.captured.push((
+                    (handle, dlci),
+                    captured_data(&stream.time, received, bytes),
+                ));
+            }
+        }
+    }
+}
+
+fn captured_data(time: &str, received: bool, bytes: Vec<u8>) -> CapturedData {
+    CapturedData {
+        time: time.to_string(),
+        direction: if received {
+            Direction::Received
+        } else {
+            Direction::Sent
+        },
+        bytes,
+    }
+}
+
+/// Formats the record timestamp as UTC `YYYY-MM-DD hh:mm:ss.mmm`.
+fn record_time(timestamp: i64) -> String {
+    let micros = timestamp.saturating_sub(EPOCH_OFFSET).max(0) as u64;
+    let time = UNIX_EPOCH + Duration::from_micros(micros);
+
+    format_time(time, " ", &format!(".{:03}", micros / 1000 % 1000))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u16_le(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const HANDLE: u16 = 0x000B;
+    const SPP_CHANNEL: u8 = 5;
+    /// 2026-10-18 05:25:15.123 UTC.
+    const TIMESTAMP: i64 = EPOCH_OFFSET + 1_792_301_115_123_000;
+
+    fn record(flags: u32, packet: &[u8]) -> Vec<u8> {
+        let mut record = Vec::new();
+        record.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+        record.extend_from_slice(&flags.to_be_bytes());
+        record.extend_from_slice(&0u32.to_be_bytes());
+        record.extend_from_slice(&TIMESTAMP.to_be_bytes());
+        record.extend_from_slice(packet);
+        record
+    }
+
+    /// Builds an H4 ACL packet with the packet boundary flag.
+    fn acl(boundary: u16, data: &[u8]) -> Vec<u8> {
+        let mut packet = vec![H4_ACL_DATA];
+        packet.extend_from_slice(&(HANDLE | boundary << 12).to_le_bytes());
+        packet.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        packet.extend_from_slice(data);
+        packet
+    }
+
+    fn l2cap(cid: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = Vec::new();
+        frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+        frame.extend_from_slice(&cid.to_le_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn rfcomm_uih(channel: u8, information: &[u8]) -> Vec<u8> {
+        let mut frame = vec![(channel << 3) | 0x03, RFCOMM_UIH];
+        frame.push(((information.len() as u8) << 1) | 0x01);
+        frame.extend_from_slice(information);
+        frame.push(0x9A);
+        frame
+    }
+
+    fn capture() -> Vec<u8> {
+        let mut file = MAGIC.to_vec();
+        file.extend_from_slice(&1u32.to_be_bytes());
+        file.extend_from_slice(&DATALINK_H4.to_be_bytes());
+
+        /* Connection request for PSM 3 from CID 0x0040, accepted with CID 0x0041. */
+        let request = [0x02, 0x07, 0x04, 0x00, 0x03, 0x00, 0x40, 0x00];
+        let response = [
+            0x03, 0x07, 0x08, 0x00, 0x41, 0x00, 0x40, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        file.extend(record(0, &acl(0x02, &l2cap(CID_SIGNALING, &request))));
+        file.extend(record(1, &acl(0x02, &l2cap(CID_SIGNALING, &response))));
+
+        /* Request split across two ACL packets. */
+        let request = l2cap(0x41, &rfcomm_uih(SPP_CHANNEL, &[0xAA, 0x01, 0xD0, 0x21, 0x94]));
+        file.extend(record(0, &acl(0x02, &request[..6])));
+        file.extend(record(0, &acl(0x01, &request[6..])));
+
+        /* Response split across two UIH frames. */
+        let response = l2cap(0x40, &rfcomm_uih(SPP_CHANNEL, &[0xBB, 0x02, 0xD0]));
+        file.extend(record(1, &acl(0x02, &response)));
+        let response = l2cap(0x40, &rfcomm_uih(SPP_CHANNEL, &[0x50, 0x21, 0xF6]));
+        file.extend(record(1, &acl(0x02, &response)));
+
+        /* Request truncated by the end of the capture. */
+        let request = l2cap(0x41, &rfcomm_uih(SPP_CHANNEL, &[0xAA, 0x01]));
+        file.extend(record(0, &acl(0x02, &request)));
+
+        /* AT commands of another RFCOMM channel. */
+        let hands_free = l2cap(0x41, &rfcomm_uih(2, b"AT+BRSF=0\r"));
+        file.extend(record(0, &acl(0x02, &hands_free)));
+
+        file
+    }
+
+    #[test]
+    fn test_parse_btsnoop() {
+        let captured = parse_btsnoop(&capture(), None).unwrap();
+
+        assert_eq!(
+            captured,
+            [
+                CapturedData {
+                    time: "2026-10-18 05:25:15.123".to_string(),
+                    direction: Direction::Sent,
+                    bytes: vec![0xAA, 0x01, 0xD0, 0x21, 0x94],
+                },
+                CapturedData {
+                    time: "2026-10-18 05:25:15.123".to_string(),
+                    direction: Direction::Received,
+                    bytes: vec![0xBB, 0x02, 0xD0, 0x50, 0x21, 0xF6],
+                },
+                CapturedData {
+                    time: "2026-10-18 05:25:15.123".to_string(),
+                    direction: Direction::Sent,
+                    bytes: vec![0xAA, 0x01],
+                },
+            ]
+        );
+
+        let hands_free = parse_btsnoop(&capture(), Some(2)).unwrap();
+        assert_eq!(hands_free.len(), 1);
+        assert_eq!(hands_free[0].bytes, b"AT+BRSF=0\r");
+
+        assert!(parse_btsnoop(b"not a capture", None).is_err());
+    }
+}
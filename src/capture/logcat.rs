@@ -0,0 +1,128 @@
+//! Parser of logcat dumps of the official Android app, which logs the exchanged frames
+//! under the `SPPUtil` tag, e.g.
+//! `10-18 05:25:15.123  1234  5678 D SPPUtil: send: AA 01 C8 21 8C`.
+use super::{CapturedData, Direction};
+use edifier::message::SIGNATURE_REQUEST;
+
+const TAG: &str = "SPPUtil";
+
+/// Extracts the frames logged under the `SPPUtil` tag, other lines are ignored.
+pub(crate) fn parse_logcat(text: &str) -> Vec<CapturedData> {
+    text.lines().filter_map(parse_line).collect()
+}
+
+fn parse_line(line: &str) -> Option<CapturedData> {
+    let tag_start = line.find(TAG)?;
+    let after_tag = &line[tag_start + TAG.len()..];
+    let message = &after_tag[after_tag.find(':')? + 1..];
+
+    let bytes = parse_hex_run(message)?;
+    let direction = message_direction(message).unwrap_or(if bytes[0] == SIGNATURE_REQUEST {
+        Direction::Sent
+    } else {
+        Direction::Received
+    });
+
+    Some(CapturedData {
+        time: line_time(&line[..tag_start]),
+        direction,
+        bytes,
+    })
+}
+
+/// Returns the `MM-DD hh:mm:ss.mmm` time of the `threadtime` and `time` formats,
+/// or an empty string when the line has none.
+fn line_time(prefix: &str) -> String {
+    let mut tokens = prefix.split_whitespace();
+    match (tokens.next(), tokens.next()) {
+        (Some(date), Some(time))
+            if date.contains('-') && time.contains(':') && date.len() <= 10 =>
+        {
+            format!("{date} {time}")
+        }
+        _ => String::new(),
+    }
+}
+
+/// Detects the direction from the words the app logs next to the frame.
+fn message_direction(message: &str) -> Option<Direction> {
+    let message = message.to_ascii_lowercase();
+    if message.contains("send") || message.contains("write") {
+        Some(Direction::Sent)
+    } else if ["read", "receive", "recv"].iter().any(|w| message.contains(w)) {
+        Some(Direction::Received)
+    } else {
+        None
+    }
+}
+
+/// Returns the longest run of hexadecimal tokens, written as separate bytes
+/// (`AA 01 C8`, `[AA, 01, C8]`) or together (`AA01C8`).
+fn parse_hex_run(message: &str) -> Option<Vec<u8>> {
+    let mut best: Vec<u8> = Vec::new();
+    let mut current = String::new();
+
+    let tokens = message
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .chain([" "]);
+    for token in tokens {
+        let token = token.trim_start_matches("0x");
+        if token.len().is_multiple_of(2) && token.chars().all(|c| c.is_ascii_hexdigit()) {
+            current.push_str(token);
+            continue;
+        }
+
+        if let Some(bytes) = decode_hex(&current)
+            && bytes.len() > best.len()
+        {
+            best = bytes;
+        }
+        current.clear();
+    }
+
+    (!best.is_empty()).then_some(best)
+}
+
+fn decode_hex(digits: &str) -> Option<Vec<u8>> {
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_logcat() {
+        let text = "\
+            10-18 05:25:15.123  1234  5678 D SPPUtil: send: AA 01 C8 21 8C\n\
+            10-18 05:25:15.130  1234  5678 I ActivityManager: Start proc 4321\n\
+            10-18 05:25:15.201  1234  5690 D SPPUtil: onRead data=[BB, 02, C3, 0D, 21, A6]\n\
+            D/SPPUtil( 1234): AA01C9218D\n\
+            10-18 05:25:16.000  1234  5678 D SPPUtil: connected\n";
+
+        assert_eq!(
+            parse_logcat(text),
+            [
+                CapturedData {
+                    time: "10-18 05:25:15.123".to_string(),
+                    direction: Direction::Sent,
+                    bytes: vec![0xAA, 0x01, 0xC8, 0x21, 0x8C],
+                },
+                CapturedData {
+                    time: "10-18 05:25:15.201".to_string(),
+                    direction: Direction::Received,
+                    bytes: vec![0xBB, 0x02, 0xC3, 0x0D, 0x21, 0xA6],
+                },
+                CapturedData {
+                    time: String::new(),
+                    direction: Direction::Sent,
+                    bytes: vec![0xAA, 0x01, 0xC9, 0x21, 0x8D],
+                },
+            ]
+        );
+    }
+}
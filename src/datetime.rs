@@ -0,0 +1,44 @@
+//! UTC date and time formatting shared by the device list and the capture timelines.
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Formats the time as UTC `YYYY-MM-DD<separator>hh:mm:ss<suffix>`.
+pub(crate) fn format_time(time: SystemTime, separator: &str, suffix: &str) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let (days, seconds) = (seconds.div_euclid(86400), seconds.rem_euclid(86400));
+
+    /* Proleptic Gregorian calendar date of the days since the Unix epoch. */
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{year:04}-{month:02}-{day:02}{separator}{:02}:{:02}:{:02}{suffix}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(UNIX_EPOCH, " ", ""), "1970-01-01 00:00:00");
+        assert_eq!(
+            format_time(UNIX_EPOCH + Duration::from_secs(951_782_400), "T", "Z"),
+            "2000-02-29T00:00:00Z"
+        );
+    }
+}
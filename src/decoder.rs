@@ -0,0 +1,155 @@
+//! The FrameDecoder struct reassembles Edifier protocol messages from a byte stream.
+//! RFCOMM reads may split a frame across several chunks or deliver several frames at once,
+//! so the decoder buffers incoming bytes and uses the length byte to cut out complete frames.
+use crate::message::{is_signature, EdifierMessage};
+
+/// Smallest valid frame: signature, length, command and CRC bytes.
+const MIN_FRAME_SIZE: usize = 5;
+
+/// Incremental decoder splitting a byte stream into Edifier protocol messages.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// Creates an empty decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends received bytes to the decoder buffer.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buffer.extend_from_slice(data);
+    }
+
+    /// Returns the next complete message, or `None` when more bytes are needed.
+    /// Bytes that do not start a valid frame are discarded.
+    pub fn next_message(&mut self) -> Option<EdifierMessage> {
+        loop {
+            if let Ok(message) = self.next_frame()? {
+                return Some(message);
+            }
+        }
+    }
+
+    /// Returns the next complete message, or the bytes that do not start a valid frame
+    /// in front of it. Returns `None` when more bytes are needed.
+    pub fn next_frame(&mut self) -> Option<Result<EdifierMessage, Vec<u8>>> {
+        let mut skipped = 0;
+        let message = loop {
+            let Some(start) = self.buffer[skipped..].iter().position(|&b| is_signature(b)) else {
+                skipped = self.buffer.len();
+                break None;
+            };
+            skipped += start;
+
+            let candidate = &self.buffer[skipped..];
+            if candidate.len() < 2 {
+                break None;
+            }
+
+            let frame_size = candidate[1] as usize + MIN_FRAME_SIZE - 1;
+            if candidate.len() < frame_size {
+                /* A complete frame further in the buffer means this candidate is garbage. */
+                match self.find_next_frame(skipped + 1) {
+                    Some(offset) => {
+                        skipped = offset;
+                        continue;
+                    }
+                    None => break None,
+                }
+            }
+
+            match decode_frame(candidate) {
+                Some(message) => break Some((message, frame_size)),
+                /* Not a real frame start, resync from the next byte. */
+                None => skipped += 1,
+            }
+        };
+
+        if skipped > 0 {
+            return Some(Err(self.buffer.drain(..skipped).collect()));
+        }
+        let (message, frame_size) = message?;
+        self.buffer.drain(..frame_size);
+
+        Some(Ok(message))
+    }
+
+    /// Takes the buffered bytes that do not form a complete frame yet, e.g. a frame
+    /// truncated at the end of a capture.
+    pub fn take_remaining(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.buffer)
+    }
+
+    /// Returns the offset of the first complete valid frame from the given offset on.
+    fn find_next_frame(&self, from: usize) -> Option<usize> {
+        (from..self.buffer.len()).find(|&offset| {
+            is_signature(self.buffer[offset]) && decode_frame(&self.buffer[offset..]).is_some()
+        })
+    }
+}
+
+/// Decodes the frame at the start of the data, if it is complete and valid.
+fn decode_frame(data: &[u8]) -> Option<EdifierMessage> {
+    let frame_size = *data.get(1)? as usize + MIN_FRAME_SIZE - 1;
+    EdifierMessage::try_from(data.get(..frame_size)?.to_vec()).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const BATTERY: [u8; 6] = [0xBB, 0x02, 0xD0, 0x50, 0x21, 0xF6];
+    const PLAYBACK: [u8; 6] = [0xBB, 0x02, 0xC3, 0x03, 0x21, 0x9C];
+
+    #[test]
+    fn test_split_frame() {
+        let mut decoder = FrameDecoder::new();
+
+        decoder.push(&BATTERY[..2]);
+        assert_eq!(decoder.next_message(), None);
+
+        decoder.push(&BATTERY[2..]);
+        assert_eq!(decoder.next_message().unwrap().as_slice(), BATTERY);
+        assert_eq!(decoder.next_message(), None);
+    }
+
+    #[test]
+    fn test_joined_frames() {
+        let mut decoder = FrameDecoder::new();
+
+        decoder.push(&[PLAYBACK.as_slice(), &BATTERY[..3]].concat());
+        assert_eq!(decoder.next_message().unwrap().as_slice(), PLAYBACK);
+        assert_eq!(decoder.next_message(), None);
+
+        decoder.push(&BATTERY[3..]);
+        assert_eq!(decoder.next_message().unwrap().as_slice(), BATTERY);
+    }
+
+    #[test]
+    fn test_resync() {
+        let mut decoder = FrameDecoder::new();
+
+        decoder.push(&[0x00, 0x13, 0xBB, 0xFF, 0xCC]);
+        decoder.push(&[BATTERY.as_slice(), &[0x42], &PLAYBACK].concat());
+        assert_eq!(decoder.next_message().unwrap().as_slice(), BATTERY);
+        assert_eq!(decoder.next_message().unwrap().as_slice(), PLAYBACK);
+        assert_eq!(decoder.next_message(), None);
+    }
+
+    #[test]
+    fn test_discarded_bytes() {
+        let mut decoder = FrameDecoder::new();
+
+        decoder.push(&[0x00, 0xBB, 0x02, 0xC3, 0x0D, 0x21, 0xA6, 0xAA, 0x01, 0xC9, 0x21, 0x8E]);
+        decoder.push(&BATTERY[..2]);
+        assert_eq!(decoder.next_frame(), Some(Err(vec![0x00])));
+        assert_eq!(decoder.next_frame().unwrap().unwrap().command_code(), 0xC3);
+        assert_eq!(decoder.next_frame(), Some(Err(vec![0xAA, 0x01, 0xC9, 0x21, 0x8E])));
+        assert_eq!(decoder.next_frame(), None);
+        assert_eq!(decoder.take_remaining(), &BATTERY[..2]);
+        assert_eq!(decoder.next_frame(), None);
+    }
+}
@@ -2,165 +2,355 @@
 //! with an Edifier device using its Serial Port Profile (SPP) service.
 //! It allows control over various device features such as game mode, LDAC mode, equalizer presets,
 //! noise cancellation modes, and more.
-use crate::message::EdifierMessage;
+use crate::bluetooth::{self, BluetoothTransport, DeviceInfo, DeviceSelector};
+use crate::error::EdifierError;
+use crate::message::{EdifierMessage, MAX_PAYLOAD_SIZE};
+use crate::transport::Transport;
 use crate::utils::join_str;
-use crate::{bluetooth, err, utils};
+use crate::utils;
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
-use strum_macros::{Display, EnumString, FromRepr};
+use std::thread;
+use std::time::{Duration, Instant};
+use strum_macros::{Display, EnumString, IntoStaticStr};
 use utils::join_hex;
-use windows::Win32::Networking::WinSock::SOCKET;
-use windows_core::GUID;
-use DenoiseMode::{Ambient, Off, On};
-
-const CMD_GET_PROMPT_VOLUME: u8 = 0x05;
-const CMD_SET_PROMPT_VOLUME: u8 = 0x06;
-const CMD_RESET_FACTORY_DEFAULTS: u8 = 0x07;
-const CMD_GET_GAME_MODE: u8 = 0x08;
-const CMD_SET_GAME_MODE: u8 = 0x09;
-const CMD_GET_LDAC_MODE: u8 = 0x48;
-const CMD_SET_LDAC_MODE: u8 = 0x49;
-const CMD_SET_NOISE_MODE: u8 = 0xC1;
-// const CMD_GET_PLAYBACK_STATUS: u8 = 0xC3;
-const CMD_SET_EQUALIZER_PRESET: u8 = 0xC4;
-const CMD_GET_FIRMWARE_VERSION: u8 = 0xC6;
-const CMD_GET_MAC_ADDRESS: u8 = 0xC8;
-const CMD_GET_NAME: u8 = 0xC9;
-const CMD_SET_NAME: u8 = 0xCA;
-const CMD_GET_NOISE_MODE: u8 = 0xCC;
-const CMD_DISCONNECT_BLUETOOTH: u8 = 0xCD;
-const CMD_POWER_OFF: u8 = 0xCE;
-const CMD_RE_PAIR: u8 = 0xCF;
-const CMD_GET_BATTERY_LEVEL: u8 = 0xD0;
-// const CMD_SET_AUTO_POWER_OFF_TIME: u8 = 0xD1;
-// const CMD_DISABLE_AUTO_POWER_OFF: u8 = 0xD2;
-// const CMD_GET_AUTO_POWER_OFF_TIME: u8 = 0xD3;
-const CMD_GET_EQUALIZER_PRESET: u8 = 0xD5;
-const CMD_GET_FINGERPRINT: u8 = 0xD8;
-const CMD_GET_BUTTON_CONTROL_SET: u8 = 0xF0;
-const CMD_SET_BUTTON_CONTROL_SET: u8 = 0xF1;
-
-pub(crate) const MAX_PROMPT_VOLUME: u8 = 15;
-pub(crate) const MAX_AMBIENT_VOLUME: u8 = 12;
-
-const SPP_UUID: GUID = GUID::from_u128(0xEDF00000_EDFE_DFED_FEDF_EDFEDFEDFEDF);
-
-/// Provides a Bluetooth client for controlling an Edifier device through its SPP service.
+use DenoiseMode::{Ambient, Off, On, Unknown};
+
+pub(crate) const CMD_GET_PROMPT_VOLUME: u8 = 0x05;
+pub(crate) const CMD_SET_PROMPT_VOLUME: u8 = 0x06;
+pub(crate) const CMD_RESET_FACTORY_DEFAULTS: u8 = 0x07;
+pub(crate) const CMD_GET_GAME_MODE: u8 = 0x08;
+pub(crate) const CMD_SET_GAME_MODE: u8 = 0x09;
+pub(crate) const CMD_GET_LDAC_MODE: u8 = 0x48;
+pub(crate) const CMD_SET_LDAC_MODE: u8 = 0x49;
+pub(crate) const CMD_SET_NOISE_MODE: u8 = 0xC1;
+pub(crate) const CMD_GET_PLAYBACK_STATUS: u8 = 0xC3;
+pub(crate) const CMD_SET_EQUALIZER_PRESET: u8 = 0xC4;
+pub(crate) const CMD_GET_FIRMWARE_VERSION: u8 = 0xC6;
+pub(crate) const CMD_GET_MAC_ADDRESS: u8 = 0xC8;
+pub(crate) const CMD_GET_NAME: u8 = 0xC9;
+pub(crate) const CMD_SET_NAME: u8 = 0xCA;
+pub(crate) const CMD_GET_NOISE_MODE: u8 = 0xCC;
+pub(crate) const CMD_DISCONNECT_BLUETOOTH: u8 = 0xCD;
+pub(crate) const CMD_POWER_OFF: u8 = 0xCE;
+pub(crate) const CMD_RE_PAIR: u8 = 0xCF;
+pub(crate) const CMD_GET_BATTERY_LEVEL: u8 = 0xD0;
+pub(crate) const CMD_SET_AUTO_POWER_OFF_TIME: u8 = 0xD1;
+pub(crate) const CMD_DISABLE_AUTO_POWER_OFF: u8 = 0xD2;
+pub(crate) const CMD_GET_AUTO_POWER_OFF_TIME: u8 = 0xD3;
+pub(crate) const CMD_GET_EQUALIZER_PRESET: u8 = 0xD5;
+pub(crate) const CMD_GET_FINGERPRINT: u8 = 0xD8;
+pub(crate) const CMD_GET_BUTTON_CONTROL_SET: u8 = 0xF0;
+pub(crate) const CMD_SET_BUTTON_CONTROL_SET: u8 = 0xF1;
+
+/// Returns the name of the command, or `None` when the command code is not known.
+pub fn command_name(command_code: u8) -> Option<&'static str> {
+    let name = match command_code {
+        CMD_GET_PROMPT_VOLUME => "Get prompt volume",
+        CMD_SET_PROMPT_VOLUME => "Set prompt volume",
+        CMD_RESET_FACTORY_DEFAULTS => "Reset factory defaults",
+        CMD_GET_GAME_MODE => "Get game mode",
+        CMD_SET_GAME_MODE => "Set game mode",
+        CMD_GET_LDAC_MODE => "Get LDAC mode",
+        CMD_SET_LDAC_MODE => "Set LDAC mode",
+        CMD_SET_NOISE_MODE => "Set noise mode",
+        CMD_GET_PLAYBACK_STATUS => "Get playback status",
+        CMD_SET_EQUALIZER_PRESET => "Set equalizer preset",
+        CMD_GET_FIRMWARE_VERSION => "Get firmware version",
+        CMD_GET_MAC_ADDRESS => "Get MAC address",
+        CMD_GET_NAME => "Get name",
+        CMD_SET_NAME => "Set name",
+        CMD_GET_NOISE_MODE => "Get noise mode",
+        CMD_DISCONNECT_BLUETOOTH => "Disconnect Bluetooth",
+        CMD_POWER_OFF => "Power off",
+        CMD_RE_PAIR => "Re-pair",
+        CMD_GET_BATTERY_LEVEL => "Get battery level",
+        CMD_SET_AUTO_POWER_OFF_TIME => "Set auto power-off time",
+        CMD_DISABLE_AUTO_POWER_OFF => "Disable auto power-off",
+        CMD_GET_AUTO_POWER_OFF_TIME => "Get auto power-off time",
+        CMD_GET_EQUALIZER_PRESET => "Get equalizer preset",
+        CMD_GET_FINGERPRINT => "Get fingerprint",
+        CMD_GET_BUTTON_CONTROL_SET => "Get button control set",
+        CMD_SET_BUTTON_CONTROL_SET => "Set button control set",
+        _ => return None,
+    };
+
+    Some(name)
+}
+
+/// Commands never sent by `probe_command`: they reset, disconnect, unpair or power off
+/// the device, restart its Bluetooth link or change a setting. Setters are sent without
+/// their payload by the probe, how the firmware handles the missing value is unknown.
+pub const PROBE_DENYLIST: [u8; 13] = [
+    CMD_SET_PROMPT_VOLUME,
+    CMD_RESET_FACTORY_DEFAULTS,
+    CMD_SET_GAME_MODE,
+    CMD_SET_LDAC_MODE,
+    CMD_SET_NOISE_MODE,
+    CMD_SET_EQUALIZER_PRESET,
+    CMD_SET_NAME,
+    CMD_DISCONNECT_BLUETOOTH,
+    CMD_POWER_OFF,
+    CMD_RE_PAIR,
+    CMD_SET_AUTO_POWER_OFF_TIME,
+    CMD_DISABLE_AUTO_POWER_OFF,
+    CMD_SET_BUTTON_CONTROL_SET,
+];
+
+/// Highest prompt voice volume level.
+pub const MAX_PROMPT_VOLUME: u8 = 15;
+/// Highest ambient sound volume level.
+pub const MAX_AMBIENT_VOLUME: u8 = 12;
+/// Shortest auto power-off time in minutes.
+pub const MIN_AUTO_POWER_OFF_TIME: u16 = 5;
+/// Longest auto power-off time in minutes.
+pub const MAX_AUTO_POWER_OFF_TIME: u16 = 180;
+
+/// UUID of the Edifier SPP service.
+pub const SPP_UUID: u128 = 0xEDF00000_EDFE_DFED_FEDF_EDFEDFEDFEDF;
+const READ_TIMEOUT: Duration = Duration::from_secs(3);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Number of attempts and delay between them when repeating a failed operation.
+/// The delay doubles after every failed attempt.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RetryPolicy {
+    /// Total number of attempts, including the first one.
+    pub attempts: u32,
+    /// Delay before the second attempt.
+    pub delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Policy running the operation once.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        attempts: 1,
+        delay: Duration::ZERO,
+    };
+
+    /// Runs the operation until it succeeds, fails with an error that is not retryable
+    /// or the attempts are exhausted, returning the last failure.
+    fn run<R>(
+        &self,
+        retryable: impl Fn(&EdifierError) -> bool,
+        mut operation: impl FnMut() -> Result<R, EdifierError>,
+    ) -> Result<R, EdifierError> {
+        let mut delay = self.delay;
+        let mut attempt = 1;
+
+        loop {
+            match operation() {
+                Ok(result) => return Ok(result),
+                Err(e) if attempt >= self.attempts || !retryable(&e) => return Err(e),
+                Err(e) => log::debug!("Attempt {attempt} failed: {e} Retrying in {delay:?}."),
+            }
+
+            thread::sleep(delay);
+            delay *= 2;
+            attempt += 1;
+        }
+    }
+}
+
+/// Reconnection policy used after the device restarts its Bluetooth link,
+/// giving it about half a minute to come back.
+const RECONNECT_POLICY: RetryPolicy = RetryPolicy {
+    attempts: 5,
+    delay: Duration::from_secs(2),
+};
+
+/// Provides a client for controlling an Edifier device through its SPP service.
 #[derive(Debug)]
-pub struct EdifierClient {
-    socket: SOCKET,
+pub struct EdifierClient<T: Transport = BluetoothTransport> {
+    transport: T,
+    read_timeout: Duration,
+    write_timeout: Duration,
+    retry_policy: RetryPolicy,
+    reconnect_policy: RetryPolicy,
 }
 
 impl EdifierClient {
 
     /// Creates a new Edifier client connected through the device SPP Bluetooth service.
-    pub(crate) fn new() -> Result<EdifierClient, String> {
-        Ok(Self {
-            socket: bluetooth::connect(&SPP_UUID)?,
-        })
+    pub fn new() -> Result<EdifierClient, EdifierError> {
+        Ok(Self::with_transport(BluetoothTransport::connect(SPP_UUID)?))
+    }
+
+    /// Creates a new Edifier client connected to the device matching the selector.
+    pub fn connect_to(selector: &DeviceSelector) -> Result<EdifierClient, EdifierError> {
+        Ok(Self::with_transport(BluetoothTransport::connect_to(
+            SPP_UUID, selector,
+        )?))
+    }
+
+    /// Lists the devices providing the Edifier SPP service known to the system.
+    pub fn list_devices() -> Result<Vec<DeviceInfo>, EdifierError> {
+        bluetooth::list_devices(SPP_UUID)
+    }
+
+    /// Searches for nearby Edifier devices, including the ones in pairing mode,
+    /// with a Bluetooth inquiry lasting up to the specified time.
+    pub fn scan_devices(timeout: Duration) -> Result<Vec<DeviceInfo>, EdifierError> {
+        bluetooth::scan_devices(SPP_UUID, timeout)
     }
 
     /// Resets Bluetooth pairing-related services for the Edifier device.
-    pub(crate) fn pair() -> Result<(), String> {
-        bluetooth::pair(&SPP_UUID)
+    pub fn pair() -> Result<(), EdifierError> {
+        bluetooth::pair(SPP_UUID, None)
+    }
+
+    /// Resets Bluetooth pairing-related services for the Edifier device matching the selector.
+    pub fn pair_with(selector: &DeviceSelector) -> Result<(), EdifierError> {
+        bluetooth::pair(SPP_UUID, Some(selector))
+    }
+}
+
+impl<T: Transport> EdifierClient<T> {
+
+    /// Creates a new Edifier client communicating over the given transport.
+    pub fn with_transport(transport: T) -> Self {
+        Self {
+            transport,
+            read_timeout: READ_TIMEOUT,
+            write_timeout: WRITE_TIMEOUT,
+            retry_policy: RetryPolicy::NONE,
+            reconnect_policy: RECONNECT_POLICY,
+        }
+    }
+
+    /// Sets how long the client waits for the device response.
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.read_timeout = timeout;
+    }
+
+    /// Sets how long the client waits for a request to be written to the connection.
+    pub fn set_write_timeout(&mut self, timeout: Duration) {
+        self.write_timeout = timeout;
+    }
+
+    /// Sets how the client repeats queries that got no valid response.
+    /// Only queries reading the device state are repeated, commands changing it are sent once.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Sets how the client reconnects when the device restarts its Bluetooth link.
+    pub fn set_reconnect_policy(&mut self, policy: RetryPolicy) {
+        self.reconnect_policy = policy;
     }
 
     /// Returns the current Bluetooth device name.
-    pub(crate) fn get_device_name(&self) -> Result<String, String> {
-        let response = self.send(CMD_GET_NAME, None)?;
-        let payload = response.payload().unwrap();
+    pub fn get_device_name(&mut self) -> Result<String, EdifierError> {
+        let payload = self.query(CMD_GET_NAME, None, 0)?;
         let result = String::from_utf8_lossy(payload.as_ref()).to_string();
 
         Ok(result)
     }
 
     /// Sets the Bluetooth device name.
-    pub(crate) fn set_device_name(&self, name: &str) -> Result<(), String> {
+    pub fn set_device_name(&mut self, name: &str) -> Result<(), EdifierError> {
+        check_payload_size("Device name length", name.as_bytes())?;
         self.send(CMD_SET_NAME, name.as_bytes().into())?;
 
         Ok(())
     }
 
     /// Returns the device MAC address formatted as hexadecimal bytes.
-    pub(crate) fn get_mac_address(&self) -> Result<String, String> {
-        let response = self.send(CMD_GET_MAC_ADDRESS, None)?;
-        let result = join_hex(response.payload().unwrap(), ":");
+    pub fn get_mac_address(&mut self) -> Result<String, EdifierError> {
+        let payload = self.query(CMD_GET_MAC_ADDRESS, None, 6)?;
+        let result = join_hex(payload, ":");
 
         Ok(result)
     }
 
     /// Returns the current battery level percentage reported by the device.
-    pub(crate) fn get_battery_level(&self) -> Result<u8, String> {
-        let response = self.send(CMD_GET_BATTERY_LEVEL, None)?;
-        let result = response.payload().unwrap()[0];
+    pub fn get_battery_level(&mut self) -> Result<u8, EdifierError> {
+        let payload = self.query(CMD_GET_BATTERY_LEVEL, None, 1)?;
+        let result = payload[0];
 
         Ok(result)
     }
 
     /// Returns the firmware version reported by the device.
-    pub(crate) fn get_firmware_version(&self) -> Result<String, String> {
-        let response = self.send(CMD_GET_FIRMWARE_VERSION, None)?;
-        let result = join_str(response.payload().unwrap(), ".");
+    pub fn get_firmware_version(&mut self) -> Result<String, EdifierError> {
+        let payload = self.query(CMD_GET_FIRMWARE_VERSION, None, 1)?;
+        let result = join_str(payload, ".");
 
         Ok(result)
     }
 
     /// Returns the device fingerprint formatted as hexadecimal bytes.
-    pub(crate) fn get_fingerprint(&self) -> Result<String, String> {
-        let response = self.send(CMD_GET_FINGERPRINT, None)?;
-        let result = join_hex(response.payload().unwrap(), " ");
+    pub fn get_fingerprint(&mut self) -> Result<String, EdifierError> {
+        let payload = self.query(CMD_GET_FINGERPRINT, None, 1)?;
+        let result = join_hex(payload, " ");
+
+        Ok(result)
+    }
+
+    /// Returns whether audio is currently playing on the device.
+    pub fn get_playback_status(&mut self) -> Result<PlaybackStatus, EdifierError> {
+        let payload = self.query(CMD_GET_PLAYBACK_STATUS, None, 1)?;
+        let result = PlaybackStatus::from_code(payload[0]);
 
         Ok(result)
     }
 
     /// Returns the current game mode state.
-    pub(crate) fn get_game_mode(&self) -> Result<GameMode, String> {
-        let response = self.send(CMD_GET_GAME_MODE, None)?;
-        let value = response.payload().unwrap()[0];
-        let result = GameMode::from_repr(value).expect("Invalid game mode");
+    pub fn get_game_mode(&mut self) -> Result<GameMode, EdifierError> {
+        let payload = self.query(CMD_GET_GAME_MODE, None, 1)?;
+        let result = GameMode::from_code(payload[0]);
 
         Ok(result)
     }
 
     /// Enables or disables game mode.
-    pub(crate) fn set_game_mode(&self, mode: GameMode) -> Result<(), String> {
-        self.send(CMD_SET_GAME_MODE, Some(&[mode as u8]))?;
+    pub fn set_game_mode(&mut self, mode: GameMode) -> Result<(), EdifierError> {
+        self.send(CMD_SET_GAME_MODE, Some(&[mode.code()]))?;
 
         Ok(())
     }
 
     /// Returns the current LDAC mode.
-    pub(crate) fn get_ldac_mode(&self) -> Result<LdacMode, String> {
-        let response = self.send(CMD_GET_LDAC_MODE, None)?;
-        let value = response.payload().unwrap()[0];
-        let result = LdacMode::from_repr(value).expect("Invalid LDAC mode");
+    pub fn get_ldac_mode(&mut self) -> Result<LdacMode, EdifierError> {
+        let payload = self.query(CMD_GET_LDAC_MODE, None, 1)?;
+        let result = LdacMode::from_code(payload[0]);
 
         Ok(result)
     }
 
     /// Sets the LDAC mode.
-    pub(crate) fn set_ldac_mode(&self, mode: LdacMode) -> Result<(), String> {
-        self.send(CMD_SET_LDAC_MODE, Some(&[mode as u8]))?;
-        // todo: reopen bluetooth socket
+    pub fn set_ldac_mode(&mut self, mode: LdacMode) -> Result<(), EdifierError> {
+        /* The device restarts its Bluetooth link to apply the mode,
+        the link may drop before the response arrives. */
+        match self.send(CMD_SET_LDAC_MODE, Some(&[mode.code()])) {
+            Ok(_) | Err(EdifierError::Disconnected) | Err(EdifierError::Timeout) => {}
+            Err(e) => return Err(e),
+        }
+
+        self.reconnect()?;
+
+        let actual = self.get_ldac_mode()?;
+        if actual != mode {
+            return Err(EdifierError::NotApplied(format!(
+                "LDAC mode is {actual} after setting it to {mode}."
+            )));
+        }
+
         Ok(())
     }
 
     /// Returns the current noise cancellation mode.
-    pub(crate) fn get_denoise_mode(&self) -> Result<DenoiseMode, String> {
-        let response = self.send(CMD_GET_NOISE_MODE, None)?;
-        let payload = response.payload().unwrap();
-        let result = DenoiseMode::from_code(payload[0], Some(payload[1]))?;
+    pub fn get_denoise_mode(&mut self) -> Result<DenoiseMode, EdifierError> {
+        let payload = self.query(CMD_GET_NOISE_MODE, None, 1)?;
+        let result = DenoiseMode::from_code(payload[0], payload.get(1).copied());
 
         Ok(result)
     }
 
     /// Sets the noise cancellation mode and optional ambient volume.
-    pub(crate) fn set_denoise_mode(&self, mode: DenoiseMode) -> Result<(), String> {
+    pub fn set_denoise_mode(&mut self, mode: DenoiseMode) -> Result<(), EdifierError> {
         let payload = match mode {
-            Ambient(volume) => match volume {
-                None => vec![mode.code()],
-                Some(v) => vec![mode.code(), v],
-            },
+            Ambient(Some(volume)) => vec![mode.code(), volume],
             _ => vec![mode.code()],
         };
 
@@ -170,49 +360,51 @@ impl EdifierClient {
     }
 
     /// Returns the current equalizer preset.
-    pub(crate) fn get_equalizer_preset(&self) -> Result<EqualizerPreset, String> {
-        let response = self.send(CMD_GET_EQUALIZER_PRESET, None)?;
-        let value = response.payload().unwrap()[0];
-        let result = EqualizerPreset::from_repr(value).expect("Invalid equalizer preset");
+    pub fn get_equalizer_preset(&mut self) -> Result<EqualizerPreset, EdifierError> {
+        let payload = self.query(CMD_GET_EQUALIZER_PRESET, None, 1)?;
+        let result = EqualizerPreset::from_code(payload[0]);
 
         Ok(result)
     }
 
     /// Sets the equalizer preset.
-    pub(crate) fn set_equalizer_preset(&self, preset: EqualizerPreset) -> Result<(), String> {
-        self.send(CMD_SET_EQUALIZER_PRESET, Some(&[preset as u8]))?;
+    pub fn set_equalizer_preset(&mut self, preset: EqualizerPreset) -> Result<(), EdifierError> {
+        self.send(CMD_SET_EQUALIZER_PRESET, Some(&[preset.code()]))?;
 
         Ok(())
     }
 
     /// Returns the configured button control set.
-    pub(crate) fn get_button_control_set(&self) -> Result<ButtonControlSet, String> {
-        let response = self.send(CMD_GET_BUTTON_CONTROL_SET, Some(&[0x0A]))?;
-        let value = response.payload().unwrap()[1];
-        let result = ButtonControlSet::from_repr(value).expect("Invalid button control set");
+    pub fn get_button_control_set(&mut self) -> Result<ButtonControlSet, EdifierError> {
+        let payload = self.query(CMD_GET_BUTTON_CONTROL_SET, Some(&[0x0A]), 2)?;
+        let result = ButtonControlSet::from_code(payload[1]);
 
         Ok(result)
     }
 
     /// Sets the button control configuration.
-    pub(crate) fn set_button_control_set(&self, set: ButtonControlSet) -> Result<(), String> {
-        self.send(CMD_SET_BUTTON_CONTROL_SET, Some(&[0x0A, set as u8]))?;
+    pub fn set_button_control_set(&mut self, set: ButtonControlSet) -> Result<(), EdifierError> {
+        self.send(CMD_SET_BUTTON_CONTROL_SET, Some(&[0x0A, set.code()]))?;
 
         Ok(())
     }
 
     /// Returns the current prompt volume.
-    pub(crate) fn get_prompt_volume(&self) -> Result<u8, String> {
-        let response = self.send(CMD_GET_PROMPT_VOLUME, None)?;
-        let result = response.payload().unwrap()[0];
+    pub fn get_prompt_volume(&mut self) -> Result<u8, EdifierError> {
+        let payload = self.query(CMD_GET_PROMPT_VOLUME, None, 1)?;
+        let result = payload[0];
 
         Ok(result)
     }
 
     /// Sets the prompt volume.
-    pub(crate) fn set_prompt_volume(&self, volume: u8) -> Result<(), String> {
+    pub fn set_prompt_volume(&mut self, volume: u8) -> Result<(), EdifierError> {
         if volume > MAX_PROMPT_VOLUME {
-            err!("Prompt volume must be from 0 to {MAX_PROMPT_VOLUME}.")
+            Err(EdifierError::OutOfRange {
+                name: "Prompt volume",
+                min: 0,
+                max: MAX_PROMPT_VOLUME as u32,
+            })
         } else {
             self.send(CMD_SET_PROMPT_VOLUME, Some(&[volume]))?;
 
@@ -220,119 +412,383 @@ impl EdifierClient {
         }
     }
 
-    /// Puts the device into re-pairing mode.
-    pub(crate) fn unpair(&self) -> Result<(), String> {
-        self.send(CMD_RE_PAIR, None)?;
+    /// Returns the auto power-off timer setting.
+    pub fn get_auto_power_off(&mut self) -> Result<AutoPowerOff, EdifierError> {
+        let payload = self.query(CMD_GET_AUTO_POWER_OFF_TIME, None, 2)?;
+        let minutes = u16::from_be_bytes([payload[0], payload[1]]);
+        let result = if minutes == 0 {
+            AutoPowerOff::Disabled
+        } else {
+            AutoPowerOff::Minutes(minutes)
+        };
 
-        Ok(())
+        Ok(result)
     }
 
-    /// Disconnects the current Bluetooth connection from the device side.
-    pub(crate) fn disconnect_bluetooth(&self) -> Result<(), String> {
-        self.send(CMD_DISCONNECT_BLUETOOTH, None)?;
+    /// Sets the time in minutes after which the idle device powers off.
+    pub fn set_auto_power_off(&mut self, minutes: u16) -> Result<(), EdifierError> {
+        if !(MIN_AUTO_POWER_OFF_TIME..=MAX_AUTO_POWER_OFF_TIME).contains(&minutes) {
+            Err(auto_power_off_range_error())
+        } else {
+            self.send(CMD_SET_AUTO_POWER_OFF_TIME, Some(&minutes.to_be_bytes()))?;
 
-        Ok(())
+            Ok(())
+        }
     }
 
-    /// Powers off the device.
-    pub(crate) fn power_off(&self) -> Result<(), String> {
-        self.send(CMD_POWER_OFF, None)?;
+    /// Disables the auto power-off timer.
+    pub fn disable_auto_power_off(&mut self) -> Result<(), EdifierError> {
+        self.send(CMD_DISABLE_AUTO_POWER_OFF, None)?;
 
         Ok(())
     }
 
+    /// Puts the device into re-pairing mode.
+    pub fn unpair(&mut self) -> Result<(), EdifierError> {
+        self.post(CMD_RE_PAIR, None)
+    }
+
+    /// Disconnects the current Bluetooth connection from the device side.
+    pub fn disconnect_bluetooth(&mut self) -> Result<(), EdifierError> {
+        self.post(CMD_DISCONNECT_BLUETOOTH, None)
+    }
+
+    /// Powers off the device.
+    pub fn power_off(&mut self) -> Result<(), EdifierError> {
+        self.post(CMD_POWER_OFF, None)
+    }
+
     /// Resets the device to factory defaults.
-    pub(crate) fn reset_factory_defaults(&self) -> Result<(), String> {
-        self.send(CMD_RESET_FACTORY_DEFAULTS, None)?;
+    pub fn reset_factory_defaults(&mut self) -> Result<(), EdifierError> {
+        self.post(CMD_RESET_FACTORY_DEFAULTS, None)
+    }
 
-        Ok(())
+    /// Sends an arbitrary command and returns the device response frame.
+    /// Meant for exploring commands the client does not implement.
+    pub fn send_raw(
+        &mut self,
+        command_code: u8,
+        payload: Option<&[u8]>,
+    ) -> Result<EdifierMessage, EdifierError> {
+        if let Some(payload) = payload {
+            check_payload_size("Payload size", payload)?;
+        }
+        self.send(command_code, payload)
+    }
+
+    /// Sends the command without a payload and returns the response frame, or `None` when
+    /// the device does not answer it. Commands of the `PROBE_DENYLIST` are refused.
+    pub fn probe_command(
+        &mut self,
+        command_code: u8,
+    ) -> Result<Option<EdifierMessage>, EdifierError> {
+        if PROBE_DENYLIST.contains(&command_code) {
+            return Err(EdifierError::InvalidValue(format!(
+                "Command [{command_code:#04X}] is not probed, it may disrupt the device."
+            )));
+        }
+
+        match self.send(command_code, None) {
+            Ok(response) => Ok(Some(response)),
+            Err(EdifierError::Timeout | EdifierError::UnexpectedResponse { .. }) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Sends the command and returns the response payload of at least `min_size` bytes.
+    /// Queries are repeated according to the retry policy when the response is missing or invalid.
+    fn query(
+        &mut self,
+        command_code: u8,
+        payload: Option<&[u8]>,
+        min_size: usize,
+    ) -> Result<Vec<u8>, EdifierError> {
+        let policy = self.retry_policy;
+        policy.run(is_transient, || self.query_once(command_code, payload, min_size))
     }
 
-    fn send(&self, command_code: u8, payload: Option<&[u8]>) -> Result<EdifierMessage, String> {
+    fn query_once(
+        &mut self,
+        command_code: u8,
+        payload: Option<&[u8]>,
+        min_size: usize,
+    ) -> Result<Vec<u8>, EdifierError> {
+        let response = self.send(command_code, payload)?;
+        let payload = response.payload().unwrap_or_default();
+
+        if payload.len() < min_size {
+            return Err(EdifierError::MalformedFrame(format!(
+                "response [{command_code:#04X}] payload has {} bytes, expected at least {min_size}",
+                payload.len()
+            )));
+        }
+
+        Ok(payload)
+    }
+
+    /// Waits for the device to come back and reopens the connection to it.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        self.transport.close();
+        thread::sleep(self.reconnect_policy.delay);
+
+        let transport = &mut self.transport;
+        self.reconnect_policy.run(|_| true, || transport.reconnect())
+    }
+
+    fn send(
+        &mut self,
+        command_code: u8,
+        payload: Option<&[u8]>,
+    ) -> Result<EdifierMessage, EdifierError> {
         let request = EdifierMessage::new(command_code, payload);
-        let response: EdifierMessage = bluetooth::send(self.socket, request.as_slice())?.into();
+        self.discard_pending()?;
+        self.transport.send(&request, self.write_timeout)?;
 
-        /*if response.command_code() != request.command_code() {
-            //todo: is [BB, 02, C3, 0D, 21, A6] an error?
-            return format_err!(
-                "Response {} does not match request command [{:#04X}]",
-                response,
-                request.command_code().unwrap()
-            );
-        }*/
+        self.receive_response(request.command_code())
+    }
 
-        Ok(response)
+    /// Drops the frames received before the request. A notification pushed by the device
+    /// or a late response to a timed out request would be taken for the response otherwise.
+    fn discard_pending(&mut self) -> Result<(), EdifierError> {
+        loop {
+            match self.transport.receive(Duration::ZERO) {
+                Ok(frame) => log::debug!("Dropped frame received before the request: {frame}."),
+                Err(EdifierError::Timeout) => return Ok(()),
+                Err(e) => return Err(e),
+            }
+        }
     }
+
+    /// Sends the command the device does not respond to.
+    fn post(&mut self, command_code: u8, payload: Option<&[u8]>) -> Result<(), EdifierError> {
+        let request = EdifierMessage::new(command_code, payload);
+        self.transport.send(&request, self.write_timeout)
+    }
+
+    /// Waits for the response to the specified command. The device may push unrelated frames
+    /// at any time (e.g. playback status `[BB, 02, C3, 0D, 21, A6]`), those are skipped.
+    fn receive_response(&mut self, command_code: u8) -> Result<EdifierMessage, EdifierError> {
+        let deadline = Instant::now() + self.read_timeout;
+        let mut unexpected_code = None;
+
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if timeout.is_zero() {
+                break;
+            }
+
+            match self.transport.receive(timeout) {
+                Ok(response) if response.command_code() == command_code => return Ok(response),
+                Ok(response) => {
+                    log::debug!("Skipped frame unrelated to [{command_code:#04X}]: {response}.");
+                    unexpected_code = Some(response.command_code());
+                }
+                Err(EdifierError::Timeout) => break,
+                Err(e) => return Err(e),
+            }
+        }
+
+        match unexpected_code {
+            Some(actual) => Err(EdifierError::UnexpectedResponse {
+                expected: command_code,
+                actual,
+            }),
+            None => Err(EdifierError::Timeout),
+        }
+    }
+}
+
+/// Checks if the failure may not repeat when the query is sent again.
+fn is_transient(error: &EdifierError) -> bool {
+    matches!(
+        error,
+        EdifierError::Timeout
+            | EdifierError::UnexpectedResponse { .. }
+            | EdifierError::MalformedFrame(_)
+            | EdifierError::CrcMismatch { .. }
+    )
+}
+
+/// Checks that the payload fits in a frame.
+fn check_payload_size(name: &'static str, payload: &[u8]) -> Result<(), EdifierError> {
+    if payload.len() > MAX_PAYLOAD_SIZE {
+        return Err(EdifierError::OutOfRange {
+            name,
+            min: 0,
+            max: MAX_PAYLOAD_SIZE as u32,
+        });
+    }
+
+    Ok(())
 }
 
-impl Drop for EdifierClient {
+impl<T: Transport> Drop for EdifierClient<T> {
     fn drop(&mut self) {
-        bluetooth::disconnect(self.socket);
+        self.transport.close();
     }
 }
 
-/*
-    #[derive(Debug, Copy, Clone, FromRepr, EnumString, Display)]
-    #[repr(u8)]
-    #[strum(ascii_case_insensitive)]
-    pub enum PlaybackStatus {
-        Stopped = 0x03,
-        Playing = 0x0D,
+/// Audio playback state reported by the device.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Display)]
+pub enum PlaybackStatus {
+    Stopped,
+    Playing,
+    #[strum(to_string = "Unknown ({0:#04X})")]
+    Unknown(u8),
+}
+
+impl PlaybackStatus {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x03 => PlaybackStatus::Stopped,
+            0x0D => PlaybackStatus::Playing,
+            _ => PlaybackStatus::Unknown(code),
+        }
     }
-*/
+}
+
+/// Implements `Display` for the enums whose `Unknown` variant is disabled in strum:
+/// unknown codes are printed, but not accepted when parsing.
+macro_rules! impl_display_with_unknown {
+    ($($name:ident),+) => {$(
+        impl Display for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $name::Unknown(code) => write!(f, "Unknown ({code:#04X})"),
+                    known => f.write_str(known.into()),
+                }
+            }
+        }
+    )+};
+}
 
-#[derive(Debug, Copy, Clone, FromRepr, EnumString, Display)]
-#[repr(u8)]
+impl_display_with_unknown!(GameMode, LdacMode, EqualizerPreset, ButtonControlSet);
+
+/// Low-latency game mode state.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString, IntoStaticStr)]
 #[strum(ascii_case_insensitive)]
 pub enum GameMode {
-    Off = 0x00,
-    On = 0x01,
+    Off,
+    On,
+    #[strum(disabled)]
+    Unknown(u8),
+}
+
+impl GameMode {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => GameMode::Off,
+            0x01 => GameMode::On,
+            _ => GameMode::Unknown(code),
+        }
+    }
+
+    /// Returns the protocol code for this game mode.
+    pub fn code(&self) -> u8 {
+        match self {
+            GameMode::Off => 0x00,
+            GameMode::On => 0x01,
+            GameMode::Unknown(code) => *code,
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone, FromRepr, EnumString, Display)]
-#[repr(u8)]
+/// LDAC codec sampling rate, or `Off` to use the default codec.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString, IntoStaticStr)]
 #[strum(ascii_case_insensitive)]
 pub enum LdacMode {
-    Off = 0x00,
+    Off,
     #[strum(serialize = "48K")]
-    K48 = 0x01,
+    K48,
     #[strum(serialize = "96K")]
-    K96 = 0x02,
+    K96,
+    #[strum(disabled)]
+    Unknown(u8),
 }
 
-#[derive(Debug, Copy, Clone, FromRepr, EnumString, Display)]
-#[repr(u8)]
+impl LdacMode {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => LdacMode::Off,
+            0x01 => LdacMode::K48,
+            0x02 => LdacMode::K96,
+            _ => LdacMode::Unknown(code),
+        }
+    }
+
+    /// Returns the protocol code for this LDAC mode.
+    pub fn code(&self) -> u8 {
+        match self {
+            LdacMode::Off => 0x00,
+            LdacMode::K48 => 0x01,
+            LdacMode::K96 => 0x02,
+            LdacMode::Unknown(code) => *code,
+        }
+    }
+}
+
+/// Built-in equalizer preset.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString, IntoStaticStr)]
 #[strum(ascii_case_insensitive)]
 pub enum EqualizerPreset {
-    Default = 0x00, /* AKA "Classic" */
-    Pop = 0x01,
-    Classical = 0x02,
-    Rock = 0x03,
+    Default, /* AKA "Classic" */
+    Pop,
+    Classical,
+    Rock,
+    #[strum(disabled)]
+    Unknown(u8),
+}
+
+impl EqualizerPreset {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => EqualizerPreset::Default,
+            0x01 => EqualizerPreset::Pop,
+            0x02 => EqualizerPreset::Classical,
+            0x03 => EqualizerPreset::Rock,
+            _ => EqualizerPreset::Unknown(code),
+        }
+    }
+
+    /// Returns the protocol code for this equalizer preset.
+    pub fn code(&self) -> u8 {
+        match self {
+            EqualizerPreset::Default => 0x00,
+            EqualizerPreset::Pop => 0x01,
+            EqualizerPreset::Classical => 0x02,
+            EqualizerPreset::Rock => 0x03,
+            EqualizerPreset::Unknown(code) => *code,
+        }
+    }
 }
 
+/// Noise cancellation mode, optionally with the ambient sound volume.
 #[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
 pub enum DenoiseMode {
     Off,
     On,
     Ambient(Option<u8>),
+    Unknown(u8),
 }
 
 impl DenoiseMode {
-    fn from_name(name: &str, volume: Option<u8>) -> Result<Self, String> {
+    fn from_name(name: &str, volume: Option<u8>) -> Result<Self, EdifierError> {
         match name.trim().to_lowercase().as_str() {
             "off" => Ok(Off),
             "on" => Ok(On),
             "ambient" => Ok(Ambient(volume)),
-            _ => err!("Illegal noise cancellation mode name"),
+            _ => Err(EdifierError::InvalidValue(format!(
+                "Illegal noise cancellation mode name: `{name}`."
+            ))),
         }
     }
 
-    fn from_code(code: u8, volume: Option<u8>) -> Result<Self, String> {
+    fn from_code(code: u8, volume: Option<u8>) -> Self {
         match code {
-            0x01 => Ok(Off),
-            0x02 => Ok(On),
-            0x03 => Ok(Ambient(volume)),
-            _ => err!("Illegal noise cancellation mode code"),
+            0x01 => Off,
+            0x02 => On,
+            0x03 => Ambient(volume),
+            _ => Unknown(code),
         }
     }
 
@@ -342,6 +798,7 @@ impl DenoiseMode {
             Off => 0x01,
             On => 0x02,
             Ambient(_) => 0x03,
+            Unknown(code) => *code,
         }
     }
 }
@@ -355,25 +812,30 @@ impl Display for DenoiseMode {
                 None => "Ambient".to_string(),
                 Some(v) => format!("Ambient (volume: {v} of {MAX_AMBIENT_VOLUME})"),
             },
+            Unknown(code) => format!("Unknown ({code:#04X})"),
         };
         write!(f, "{s}")
     }
 }
 
 impl FromStr for DenoiseMode {
-    type Err = String;
+    type Err = EdifierError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         let split: Vec<_> = s.split('-').collect();
 
         let volume = if split.len() == 2 {
             let sv = split[1];
-            let v = sv
-                .parse()
-                .map_err(|_| format!("Invalid ambient volume value: `{sv}`."))?;
+            let v = sv.parse().map_err(|_| {
+                EdifierError::InvalidValue(format!("Invalid ambient volume value: `{sv}`."))
+            })?;
 
             if v > MAX_AMBIENT_VOLUME {
-                err!("Ambient volume must be from 0 to {MAX_AMBIENT_VOLUME}.")?
+                return Err(EdifierError::OutOfRange {
+                    name: "Ambient volume",
+                    min: 0,
+                    max: MAX_AMBIENT_VOLUME as u32,
+                });
             } else {
                 Some(v)
             }
@@ -385,44 +847,137 @@ impl FromStr for DenoiseMode {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, FromRepr, EnumString, Display)]
-#[repr(u8)]
+/// Idle time after which the device powers itself off.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AutoPowerOff {
+    Disabled,
+    Minutes(u16),
+}
+
+impl Display for AutoPowerOff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AutoPowerOff::Disabled => write!(f, "Off"),
+            AutoPowerOff::Minutes(minutes) => write!(f, "{minutes} min"),
+        }
+    }
+}
+
+impl FromStr for AutoPowerOff {
+    type Err = EdifierError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("off") {
+            return Ok(AutoPowerOff::Disabled);
+        }
+
+        let minutes = s.parse().map_err(|_| {
+            EdifierError::InvalidValue(format!("Invalid auto power-off time: `{s}`."))
+        })?;
+
+        if !(MIN_AUTO_POWER_OFF_TIME..=MAX_AUTO_POWER_OFF_TIME).contains(&minutes) {
+            Err(auto_power_off_range_error())
+        } else {
+            Ok(AutoPowerOff::Minutes(minutes))
+        }
+    }
+}
+
+fn auto_power_off_range_error() -> EdifierError {
+    EdifierError::OutOfRange {
+        name: "Auto power-off time",
+        min: MIN_AUTO_POWER_OFF_TIME as u32,
+        max: MAX_AUTO_POWER_OFF_TIME as u32,
+    }
+}
+
+/// Sequence of noise cancellation modes cycled by the round control button.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString, IntoStaticStr)]
 #[strum(ascii_case_insensitive)]
 pub enum ButtonControlSet {
     #[strum(serialize = "Default")]
     #[strum(serialize = "0")]
-    Default = 0x00,
+    Default,
     #[strum(serialize = "Off-On")]
     #[strum(serialize = "1")]
-    OffOn = 0x01,
+    OffOn,
     #[strum(serialize = "On-Off")]
     #[strum(serialize = "2")]
-    OnOff = 0x03,
+    OnOff,
     #[strum(serialize = "Off-Ambient")]
     #[strum(serialize = "3")]
-    OffAmbient = 0x04,
+    OffAmbient,
     #[strum(serialize = "On-Ambient")]
     #[strum(serialize = "4")]
-    OnAmbient = 0x06,
+    OnAmbient,
     #[strum(serialize = "On-Off-Ambient")]
     #[strum(serialize = "6")]
-    OnOffAmbient = 0x07,
+    OnOffAmbient,
     #[strum(serialize = "5")]
     #[strum(serialize = "Off-On-Ambient")]
-    OffOnAmbient = 0x08,
+    OffOnAmbient,
+    #[strum(disabled)]
+    Unknown(u8),
+}
+
+impl ButtonControlSet {
+    fn from_code(code: u8) -> Self {
+        match code {
+            0x00 => ButtonControlSet::Default,
+            0x01 => ButtonControlSet::OffOn,
+            0x03 => ButtonControlSet::OnOff,
+            0x04 => ButtonControlSet::OffAmbient,
+            0x06 => ButtonControlSet::OnAmbient,
+            0x07 => ButtonControlSet::OnOffAmbient,
+            0x08 => ButtonControlSet::OffOnAmbient,
+            _ => ButtonControlSet::Unknown(code),
+        }
+    }
+
+    /// Returns the protocol code for this button control set.
+    pub fn code(&self) -> u8 {
+        match self {
+            ButtonControlSet::Default => 0x00,
+            ButtonControlSet::OffOn => 0x01,
+            ButtonControlSet::OnOff => 0x03,
+            ButtonControlSet::OffAmbient => 0x04,
+            ButtonControlSet::OnAmbient => 0x06,
+            ButtonControlSet::OnOffAmbient => 0x07,
+            ButtonControlSet::OffOnAmbient => 0x08,
+            ButtonControlSet::Unknown(code) => *code,
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::device::{ButtonControlSet, DenoiseMode, EdifierClient, LdacMode};
-    use std::sync::{LazyLock, Mutex};
-
-    /// Prevents using the same socket in tests simultaneously
-    static SOCKET_GUARD: LazyLock<Mutex<()>> = LazyLock::new(|| Mutex::new(()));
+    use crate::device::{
+        AutoPowerOff, ButtonControlSet, DenoiseMode, EdifierClient, EqualizerPreset, GameMode,
+        LdacMode, PlaybackStatus, RetryPolicy, CMD_GET_AUTO_POWER_OFF_TIME, CMD_GET_BATTERY_LEVEL,
+        CMD_GET_NAME, CMD_GET_PLAYBACK_STATUS, CMD_POWER_OFF, CMD_SET_NAME,
+    };
+    use crate::error::EdifierError;
+    use crate::message::{EdifierMessage, SIGNATURE_RESPONSE};
+    use crate::simulator::{ScriptedReply, ScriptedSimulator, Simulator};
+    use std::time::Duration;
+
+    /// Battery level response without the level.
+    fn short_battery_reply() -> ScriptedReply {
+        ScriptedReply::Frames(vec![EdifierMessage::with_signature(
+            SIGNATURE_RESPONSE,
+            CMD_GET_BATTERY_LEVEL,
+            None,
+        )])
+    }
 
-    fn get_client() -> EdifierClient {
-        let _guard = SOCKET_GUARD.lock().unwrap();
-        EdifierClient::new().unwrap()
+    fn get_client() -> EdifierClient<Simulator> {
+        let mut client = EdifierClient::with_transport(Simulator::new());
+        client.set_reconnect_policy(RetryPolicy {
+            attempts: 1,
+            delay: Duration::ZERO,
+        });
+        client
     }
 
     #[test]
@@ -512,9 +1067,234 @@ mod test {
 
     #[test]
     fn test_set_ldac_mode() {
-        let result = get_client().set_ldac_mode(LdacMode::K96);
+        let mut client = get_client();
+        let result = client.set_ldac_mode(LdacMode::K96);
 
         println!("{:?}", result);
         assert!(result.is_ok());
+        assert_eq!(client.get_ldac_mode().unwrap(), LdacMode::K96);
+    }
+
+    #[test]
+    fn test_set_and_get_denoise_mode() {
+        let mut client = get_client();
+        client.set_denoise_mode(DenoiseMode::Ambient(Some(3))).unwrap();
+
+        assert_eq!(client.get_denoise_mode().unwrap(), DenoiseMode::Ambient(Some(3)));
+    }
+
+    #[test]
+    fn test_set_and_get_button_control_set() {
+        let mut client = get_client();
+        client.set_button_control_set(ButtonControlSet::OnAmbient).unwrap();
+
+        assert_eq!(client.get_button_control_set().unwrap(), ButtonControlSet::OnAmbient);
+    }
+
+    #[test]
+    fn test_skip_unrelated_frames() {
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(
+            CMD_GET_BATTERY_LEVEL,
+            ScriptedReply::Frames(vec![
+                EdifierMessage::with_signature(
+                    SIGNATURE_RESPONSE,
+                    CMD_GET_PLAYBACK_STATUS,
+                    Some(&[0x0D]),
+                ),
+                EdifierMessage::with_signature(
+                    SIGNATURE_RESPONSE,
+                    CMD_GET_BATTERY_LEVEL,
+                    Some(&[0x50]),
+                ),
+            ]),
+        );
+        let mut client = EdifierClient::with_transport(transport);
+
+        assert_eq!(client.get_battery_level().unwrap(), 80);
+    }
+
+    #[test]
+    fn test_unexpected_response() {
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(
+            0x42,
+            ScriptedReply::Frames(vec![EdifierMessage::with_signature(
+                SIGNATURE_RESPONSE,
+                CMD_GET_PLAYBACK_STATUS,
+                Some(&[0x0D]),
+            )]),
+        );
+        let mut client = EdifierClient::with_transport(transport);
+
+        assert!(matches!(
+            client.send(0x42, None),
+            Err(EdifierError::UnexpectedResponse {
+                expected: 0x42,
+                actual: CMD_GET_PLAYBACK_STATUS
+            })
+        ));
+    }
+
+    #[test]
+    fn test_unknown_enum_value() {
+        let mut client = get_client();
+        client.set_game_mode(GameMode::Unknown(0x05)).unwrap();
+
+        let result = client.get_game_mode().unwrap();
+
+        assert_eq!(result, GameMode::Unknown(0x05));
+        assert_eq!(result.to_string(), "Unknown (0x05)");
+    }
+
+    #[test]
+    fn test_parse_enum_value() {
+        assert_eq!("on".parse::<GameMode>().unwrap(), GameMode::On);
+        assert_eq!("96k".parse::<LdacMode>().unwrap(), LdacMode::K96);
+        assert_eq!("5".parse::<ButtonControlSet>().unwrap(), ButtonControlSet::OffOnAmbient);
+        assert_eq!(ButtonControlSet::OffOnAmbient.to_string(), "Off-On-Ambient");
+        assert_eq!(LdacMode::K48.to_string(), "48K");
+
+        assert!("unknown ({0:#04x})".parse::<GameMode>().is_err());
+        assert!("Unknown (0x05)".parse::<LdacMode>().is_err());
+        assert!("unknown".parse::<EqualizerPreset>().is_err());
+        assert!("Unknown".parse::<ButtonControlSet>().is_err());
+    }
+
+    #[test]
+    fn test_short_payload() {
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(CMD_GET_BATTERY_LEVEL, short_battery_reply());
+        let mut client = EdifierClient::with_transport(transport);
+
+        assert!(matches!(
+            client.get_battery_level(),
+            Err(EdifierError::MalformedFrame(_))
+        ));
+    }
+
+    #[test]
+    fn test_probe_command() {
+        let mut client = get_client();
+
+        let response = client.probe_command(CMD_GET_BATTERY_LEVEL).unwrap().unwrap();
+        assert_eq!(response.payload(), Some(vec![80]));
+        assert_eq!(client.probe_command(0x42).unwrap(), None);
+        assert!(client.probe_command(CMD_POWER_OFF).is_err());
+        assert!(client.probe_command(CMD_SET_NAME).is_err());
+    }
+
+    #[test]
+    fn test_payload_size() {
+        let mut client = get_client();
+
+        assert!(matches!(
+            client.set_device_name(&"W".repeat(255)),
+            Err(EdifierError::OutOfRange { max: 254, .. })
+        ));
+        assert!(matches!(
+            client.send_raw(CMD_GET_NAME, Some(&[0x00; 300])),
+            Err(EdifierError::OutOfRange { max: 254, .. })
+        ));
+        client.set_device_name(&"W".repeat(254)).unwrap();
+        assert_eq!(client.get_device_name().unwrap(), "W".repeat(254));
+    }
+
+    #[test]
+    fn test_query_retry() {
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(CMD_GET_BATTERY_LEVEL, short_battery_reply());
+        let mut client = EdifierClient::with_transport(transport);
+        client.set_retry_policy(RetryPolicy {
+            attempts: 2,
+            delay: Duration::ZERO,
+        });
+
+        assert_eq!(client.get_battery_level().unwrap(), 80);
+    }
+
+    #[test]
+    fn test_discard_stale_frames() {
+        let mut simulator = Simulator::new();
+        simulator.notify(EdifierMessage::with_signature(
+            SIGNATURE_RESPONSE,
+            CMD_GET_BATTERY_LEVEL,
+            Some(&[0x14]),
+        ));
+        let mut client = EdifierClient::with_transport(simulator);
+
+        assert_eq!(client.get_battery_level().unwrap(), 80);
+        assert_eq!(client.get_battery_level().unwrap(), 80);
+    }
+
+    #[test]
+    fn test_commands_without_response() {
+        let mut client = get_client();
+
+        assert!(client.disconnect_bluetooth().is_ok());
+        assert!(client.reset_factory_defaults().is_ok());
+    }
+
+    #[test]
+    fn test_auto_power_off() {
+        let mut client = get_client();
+
+        client.set_auto_power_off(30).unwrap();
+        assert_eq!(client.get_auto_power_off().unwrap(), AutoPowerOff::Minutes(30));
+
+        client.disable_auto_power_off().unwrap();
+        assert_eq!(client.get_auto_power_off().unwrap(), AutoPowerOff::Disabled);
+
+        assert!(matches!(
+            client.set_auto_power_off(181),
+            Err(EdifierError::OutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_short_auto_power_off() {
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(
+            CMD_GET_AUTO_POWER_OFF_TIME,
+            ScriptedReply::Frames(vec![EdifierMessage::with_signature(
+                SIGNATURE_RESPONSE,
+                CMD_GET_AUTO_POWER_OFF_TIME,
+                Some(&[0x00]),
+            )]),
+        );
+        let mut client = EdifierClient::with_transport(transport);
+
+        assert!(matches!(
+            client.get_auto_power_off(),
+            Err(EdifierError::MalformedFrame(_))
+        ));
+    }
+
+    #[test]
+    fn test_get_playback_status() {
+        let mut simulator = Simulator::new();
+        simulator.notify(EdifierMessage::with_signature(
+            SIGNATURE_RESPONSE,
+            CMD_GET_PLAYBACK_STATUS,
+            Some(&[0x0D]),
+        ));
+        let mut client = EdifierClient::with_transport(simulator);
+
+        /* The notification queued before the request is not its response. */
+        assert_eq!(client.get_playback_status().unwrap(), PlaybackStatus::Stopped);
+        assert_eq!(client.get_playback_status().unwrap(), PlaybackStatus::Stopped);
+
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(
+            CMD_GET_PLAYBACK_STATUS,
+            ScriptedReply::Frames(vec![EdifierMessage::with_signature(
+                SIGNATURE_RESPONSE,
+                CMD_GET_PLAYBACK_STATUS,
+                Some(&[0x0D]),
+            )]),
+        );
+        let mut client = EdifierClient::with_transport(transport);
+        assert_eq!(client.get_playback_status().unwrap(), PlaybackStatus::Playing);
+        assert_eq!(client.get_playback_status().unwrap(), PlaybackStatus::Stopped);
     }
 }
@@ -0,0 +1,111 @@
+//! The EdifierError enum describes failures of the Bluetooth connection,
+//! of the protocol exchange and of the device settings validation.
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+
+/// Error returned by the Edifier device operations.
+#[derive(Debug)]
+pub enum EdifierError {
+    /// No Bluetooth radio is available.
+    NoRadio,
+    /// No device providing the Edifier SPP service was found.
+    DeviceNotFound,
+    /// No device providing the Edifier SPP service matches the device selector.
+    NoMatchingDevice(String),
+    /// Several devices match the device selector.
+    AmbiguousDevice {
+        selector: String,
+        candidates: Vec<String>,
+    },
+    /// Connection to the device could not be established.
+    ConnectFailed(io::Error),
+    /// The device did not connect or respond in time.
+    Timeout,
+    /// The device closed the connection.
+    Disconnected,
+    /// Socket or file input/output failed.
+    Io(io::Error),
+    /// Received bytes do not form a valid protocol frame.
+    MalformedFrame(String),
+    /// Frame checksum does not match its content.
+    CrcMismatch { expected: u16, actual: u16 },
+    /// Response command code does not match the request.
+    UnexpectedResponse { expected: u8, actual: u8 },
+    /// Value is outside of the range accepted by the device.
+    OutOfRange {
+        name: &'static str,
+        min: u32,
+        max: u32,
+    },
+    /// Value could not be parsed.
+    InvalidValue(String),
+    /// The device reports a different value than the one just set.
+    NotApplied(String),
+    /// Operation is not supported by the current backend.
+    Unsupported(&'static str),
+}
+
+impl Display for EdifierError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EdifierError::NoRadio => write!(f, "No Bluetooth radio."),
+            EdifierError::DeviceNotFound => write!(f, "No devices found."),
+            EdifierError::NoMatchingDevice(selector) => {
+                write!(f, "No devices matching `{selector}` found.")
+            }
+            EdifierError::AmbiguousDevice {
+                selector,
+                candidates,
+            } => write!(
+                f,
+                "Several devices match `{selector}`: {}. Select the device by MAC address.",
+                candidates.join(", ")
+            ),
+            EdifierError::ConnectFailed(e) => write!(f, "Failed to connect to device: {e}."),
+            EdifierError::Timeout => write!(f, "Device did not respond in time."),
+            EdifierError::Disconnected => write!(f, "Connection closed by device."),
+            EdifierError::Io(e) => write!(f, "I/O error: {e}."),
+            EdifierError::MalformedFrame(reason) => write!(f, "Malformed frame: {reason}."),
+            EdifierError::CrcMismatch { expected, actual } => write!(
+                f,
+                "Frame CRC mismatch: expected {expected:#06X}, got {actual:#06X}."
+            ),
+            EdifierError::UnexpectedResponse { expected, actual } => write!(
+                f,
+                "Response command [{actual:#04X}] does not match request command [{expected:#04X}]."
+            ),
+            EdifierError::OutOfRange { name, min, max } => {
+                write!(f, "{name} must be from {min} to {max}.")
+            }
+            EdifierError::InvalidValue(message) => write!(f, "{message}"),
+            EdifierError::NotApplied(message) => write!(f, "Setting not applied: {message}"),
+            EdifierError::Unsupported(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl Error for EdifierError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EdifierError::ConnectFailed(e) | EdifierError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for EdifierError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock => EdifierError::Timeout,
+            _ => EdifierError::Io(e),
+        }
+    }
+}
+
+#[cfg(windows)]
+impl From<windows_core::Error> for EdifierError {
+    fn from(e: windows_core::Error) -> Self {
+        EdifierError::Io(io::Error::other(e.to_string()))
+    }
+}
@@ -0,0 +1,205 @@
+//! Device status report printed by the `--info` option.
+//! Every field is read independently, so a failed getter is reported next to the field
+//! instead of aborting the whole report.
+use edifier::device::MAX_PROMPT_VOLUME;
+use edifier::transport::Transport;
+use edifier::{AutoPowerOff, EdifierClient, EdifierError};
+use serde_json::{Map, Value};
+use std::fmt::Display;
+use strum_macros::EnumString;
+
+/// Output format of the device status report.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, EnumString)]
+#[strum(ascii_case_insensitive)]
+pub(crate) enum OutputFormat {
+    /// Human-readable `Label: value` lines.
+    Text,
+    /// Single JSON object, failed fields are listed under `errors`.
+    Json,
+    /// Shell-friendly `key=value` lines.
+    Kv,
+}
+
+/// Single report field with its human-readable and structured representations.
+struct InfoField {
+    key: &'static str,
+    label: &'static str,
+    value: Result<(String, Value), EdifierError>,
+}
+
+impl InfoField {
+    /// Creates a field reported as a string in structured formats.
+    fn new<V: Display>(
+        key: &'static str,
+        label: &'static str,
+        result: Result<V, EdifierError>,
+    ) -> Self {
+        Self {
+            key,
+            label,
+            value: result.map(|v| (v.to_string(), Value::from(v.to_string()))),
+        }
+    }
+
+    /// Creates a field reported as a number in structured formats.
+    fn number(
+        key: &'static str,
+        label: &'static str,
+        result: Result<u8, EdifierError>,
+        suffix: &str,
+    ) -> Self {
+        Self {
+            key,
+            label,
+            value: result.map(|v| (format!("{v}{suffix}"), Value::from(v))),
+        }
+    }
+}
+
+/// Reads the device status and prints it in the specified format.
+pub(crate) fn print_device_info(
+    client: &mut EdifierClient<impl Transport>,
+    format: OutputFormat,
+) {
+    let fields = read_device_info(client);
+
+    match format {
+        OutputFormat::Text => print_text(&fields),
+        OutputFormat::Json => println!("{}", to_json(&fields)),
+        OutputFormat::Kv => print_kv(&fields),
+    }
+}
+
+fn read_device_info(client: &mut EdifierClient<impl Transport>) -> Vec<InfoField> {
+    vec![
+        InfoField::new("name", "Device name", client.get_device_name()),
+        InfoField::new("ldac_mode", "LDAC mode", client.get_ldac_mode()),
+        InfoField::number(
+            "battery_level",
+            "Battery level",
+            client.get_battery_level(),
+            "%",
+        ),
+        auto_power_off_field(client.get_auto_power_off()),
+        InfoField::new(
+            "playback_status",
+            "Playback status",
+            client.get_playback_status(),
+        ),
+        InfoField::new(
+            "denoise_mode",
+            "Noise cancellation mode",
+            client.get_denoise_mode(),
+        ),
+        InfoField::number(
+            "prompt_volume",
+            "Prompt voice volume",
+            client.get_prompt_volume(),
+            &format!(" of {MAX_PROMPT_VOLUME}"),
+        ),
+        InfoField::new(
+            "button_control_set",
+            "Control button actions",
+            client.get_button_control_set(),
+        ),
+        InfoField::new("game_mode", "Game mode", client.get_game_mode()),
+        InfoField::new(
+            "equalizer_preset",
+            "Equalizer preset",
+            client.get_equalizer_preset(),
+        ),
+        InfoField::new("mac_address", "Mac address", client.get_mac_address()),
+        InfoField::new(
+            "firmware_version",
+            "Firmware version",
+            client.get_firmware_version(),
+        ),
+        InfoField::new("fingerprint", "Fingerprint", client.get_fingerprint()),
+    ]
+}
+
+/// Auto power-off time is reported in minutes, or as `off` like the `-t` option accepts it.
+fn auto_power_off_field(result: Result<AutoPowerOff, EdifierError>) -> InfoField {
+    InfoField {
+        key: "auto_power_off",
+        label: "Auto power-off",
+        value: result.map(|v| {
+            let value = match v {
+                AutoPowerOff::Disabled => Value::from("off"),
+                AutoPowerOff::Minutes(minutes) => Value::from(minutes),
+            };
+            (v.to_string(), value)
+        }),
+    }
+}
+
+fn print_text(fields: &[InfoField]) {
+    for field in fields {
+        match &field.value {
+            Ok((text, _)) => println!("{}: {text}", field.label),
+            Err(e) => eprintln!("{}: {e}", field.label),
+        }
+    }
+}
+
+fn print_kv(fields: &[InfoField]) {
+    for field in fields {
+        match &field.value {
+            Ok((_, Value::String(s))) => println!("{}={s}", field.key),
+            Ok((_, value)) => println!("{}={value}", field.key),
+            Err(e) => eprintln!("{}: {e}", field.key),
+        }
+    }
+}
+
+fn to_json(fields: &[InfoField]) -> Value {
+    let mut values = Map::new();
+    let mut errors = Map::new();
+
+    for field in fields {
+        match &field.value {
+            Ok((_, value)) => {
+                values.insert(field.key.to_string(), value.clone());
+            }
+            Err(e) => {
+                values.insert(field.key.to_string(), Value::Null);
+                errors.insert(field.key.to_string(), Value::from(e.to_string()));
+            }
+        }
+    }
+
+    if !errors.is_empty() {
+        values.insert("errors".to_string(), Value::Object(errors));
+    }
+
+    Value::Object(values)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_to_json() {
+        let fields = vec![
+            InfoField::new("name", "Device name", Ok("EDIFIER W820NB")),
+            InfoField::number("battery_level", "Battery level", Ok(80), "%"),
+            auto_power_off_field(Ok(AutoPowerOff::Disabled)),
+            InfoField::new::<String>("game_mode", "Game mode", Err(EdifierError::Timeout)),
+        ];
+
+        assert_eq!(
+            to_json(&fields),
+            json!({
+                "name": "EDIFIER W820NB",
+                "battery_level": 80,
+                "auto_power_off": "off",
+                "game_mode": null,
+                "errors": {
+                    "game_mode": "Device did not respond in time."
+                }
+            })
+        );
+    }
+}
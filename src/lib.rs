@@ -0,0 +1,37 @@
+//! Control library for Edifier Bluetooth headsets speaking the vendor SPP protocol.
+//!
+//! [`EdifierClient`] sends protocol commands over a [`Transport`], which is either a
+//! [`BluetoothTransport`] connected to a real device, the in-process [`Simulator`] or a
+//! [`ReplayTransport`] answering from a session recorded by [`RecordingTransport`].
+//!
+//! ```
+//! use edifier::{EdifierClient, GameMode, Simulator};
+//!
+//! let mut client = EdifierClient::with_transport(Simulator::new());
+//! client.set_game_mode(GameMode::On)?;
+//! assert_eq!(client.get_game_mode()?, GameMode::On);
+//! # Ok::<(), edifier::EdifierError>(())
+//! ```
+mod bluetooth;
+pub mod decoder;
+pub mod device;
+pub mod error;
+pub mod message;
+pub mod session;
+pub mod settings;
+pub mod simulator;
+pub mod transport;
+mod utils;
+
+pub use bluetooth::{BluetoothTransport, DeviceInfo, DeviceSelector};
+pub use decoder::FrameDecoder;
+pub use device::{
+    command_name, AutoPowerOff, ButtonControlSet, DenoiseMode, EdifierClient, EqualizerPreset,
+    GameMode, LdacMode, PlaybackStatus, RetryPolicy, PROBE_DENYLIST, SPP_UUID,
+};
+pub use error::EdifierError;
+pub use message::EdifierMessage;
+pub use session::{RecordingTransport, ReplayTransport};
+pub use settings::DeviceSettings;
+pub use simulator::{ScriptedReply, ScriptedSimulator, Simulator};
+pub use transport::Transport;
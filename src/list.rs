@@ -0,0 +1,96 @@
+//! Device inventory printed by the `list` subcommand.
+use edifier::DeviceInfo;
+use crate::datetime::format_time;
+use serde_json::{json, Value};
+
+/// Prints the devices as text lines or as a JSON array.
+pub(crate) fn print_devices(devices: &[DeviceInfo], json: bool) {
+    if json {
+        let values: Vec<_> = devices.iter().map(device_json).collect();
+        println!("{}", Value::Array(values));
+        return;
+    }
+
+    if devices.is_empty() {
+        println!("No devices found.");
+    }
+
+    for device in devices {
+        println!("{}", device_text(device));
+    }
+}
+
+fn device_text(device: &DeviceInfo) -> String {
+    let flags: Vec<_> = [
+        (device.connected, "connected"),
+        (device.remembered, "remembered"),
+        (device.authenticated, "authenticated"),
+    ]
+    .iter()
+    .filter_map(|&(set, flag)| set.then_some(flag))
+    .collect();
+
+    let mut text = format!(
+        "{}  {}  [{}]",
+        device.address_string(),
+        device.name.as_deref().unwrap_or("<unknown>"),
+        flags.join(", ")
+    );
+    if let Some(time) = device.last_seen {
+        text.push_str(&format!("  last seen: {}", format_time(time, " ", " UTC")));
+    }
+    if let Some(time) = device.last_used {
+        text.push_str(&format!("  last used: {}", format_time(time, " ", " UTC")));
+    }
+
+    text
+}
+
+fn device_json(device: &DeviceInfo) -> Value {
+    json!({
+        "address": device.address_string(),
+        "name": device.name,
+        "connected": device.connected,
+        "remembered": device.remembered,
+        "authenticated": device.authenticated,
+        "last_seen": device.last_seen.map(|t| format_time(t, "T", "Z")),
+        "last_used": device.last_used.map(|t| format_time(t, "T", "Z")),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_device_output() {
+        let device = DeviceInfo {
+            address: [0x00, 0x1E, 0x7C, 0x12, 0x34, 0x56],
+            name: Some("EDIFIER W820NB".to_string()),
+            connected: true,
+            remembered: true,
+            authenticated: false,
+            last_seen: Some(UNIX_EPOCH + Duration::from_secs(1_792_301_115)),
+            last_used: None,
+        };
+
+        assert_eq!(
+            device_text(&device),
+            "00:1E:7C:12:34:56  EDIFIER W820NB  [connected, remembered]  \
+            last seen: 2026-10-18 05:25:15 UTC"
+        );
+        assert_eq!(
+            device_json(&device),
+            json!({
+                "address": "00:1E:7C:12:34:56",
+                "name": "EDIFIER W820NB",
+                "connected": true,
+                "remembered": true,
+                "authenticated": false,
+                "last_seen": "2026-10-18T05:25:15Z",
+                "last_used": null,
+            })
+        );
+    }
+}
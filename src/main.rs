@@ -1,22 +1,92 @@
-use crate::device::{
-    ButtonControlSet, DenoiseMode, EdifierClient, EqualizerPreset, GameMode, LdacMode,
-    MAX_PROMPT_VOLUME,
-};
+use crate::capture::btsnoop::parse_btsnoop;
+use crate::capture::logcat::parse_logcat;
+use crate::capture::{format_timeline, CapturedData};
+use crate::info::{print_device_info, OutputFormat};
+use crate::list::print_devices;
+use crate::preset::{delete_preset, list_presets, load_preset, presets_dir, save_preset};
+use crate::probe::{format_report, probe_commands, ProbeResult};
+use crate::profile::{export_settings, read_settings_file};
+use crate::raw::{describe_message, parse_hex_byte, parse_hex_bytes};
 use argh::FromArgs;
-use std::env;
-use std::io::{stdin, stdout, Write};
-
-mod bluetooth;
-mod device;
-mod message;
-mod utils;
+use edifier::transport::Transport;
+use edifier::{
+    AutoPowerOff, BluetoothTransport, ButtonControlSet, DenoiseMode, DeviceSelector,
+    EdifierClient, EdifierError, EdifierMessage, EqualizerPreset, GameMode, LdacMode,
+    RecordingTransport, ReplayTransport, RetryPolicy, SPP_UUID,
+};
+use std::fs;
+use std::io::{stdin, stdout, IsTerminal, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+mod capture;
+mod datetime;
+mod info;
+mod list;
+mod preset;
+mod probe;
+mod profile;
+mod raw;
+
+/// Client over the Bluetooth connection, or over the session recording or replay.
+type Client = EdifierClient<Box<dyn Transport>>;
+
+/// Delay before the first retry of a query, doubled for every next retry.
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+/// Longest inquiry in seconds, the radio counts it in at most 48 units of 1.28 s.
+const MAX_SCAN_TIMEOUT: u64 = 61;
 
 #[derive(FromArgs)]
 #[argh(description = "Tool to control Edifier devices")]
 struct Args {
+    #[argh(
+        option,
+        short = 'm',
+        description = "select device by MAC address or name",
+        arg_name = "mac|name"
+    )]
+    device: Option<DeviceSelector>,
+
     #[argh(switch, short = 'i', description = "print device current status")]
     info: bool,
 
+    #[argh(
+        option,
+        short = 'f',
+        description = "print device current status in the format [text|json|kv], default text",
+        arg_name = "text|json|kv"
+    )]
+    format: Option<OutputFormat>,
+
+    #[argh(
+        option,
+        from_str_fn(parse_timeout),
+        description = "device response timeout in seconds, fractions allowed, default 3",
+        arg_name = "seconds"
+    )]
+    timeout: Option<Duration>,
+
+    #[argh(
+        option,
+        description = "retries of queries the device did not answer, default 0",
+        arg_name = "count"
+    )]
+    retries: Option<u32>,
+
+    #[argh(
+        option,
+        description = "record the exchanged frames to a JSON Lines session file",
+        arg_name = "file"
+    )]
+    record: Option<PathBuf>,
+
+    #[argh(
+        option,
+        description = "answer requests from a recorded session file instead of the device",
+        arg_name = "file"
+    )]
+    replay: Option<PathBuf>,
+
     #[argh(option, short = 'a', description = "set device name")]
     name: Option<String>,
 
@@ -69,6 +139,14 @@ struct Args {
     )]
     button: Option<ButtonControlSet>,
 
+    #[argh(
+        option,
+        short = 't',
+        description = "set auto power-off time in minutes [5-180|off]",
+        arg_name = "5-180|off"
+    )]
+    auto_off: Option<AutoPowerOff>,
+
     #[argh(switch, short = 'd', description = "disconnect device")]
     disconnect: bool,
 
@@ -90,31 +168,260 @@ struct Args {
         description = "skip confirmation for unsafe operations"
     )]
     no_confirm: bool,
+
+    #[argh(subcommand)]
+    command: Option<Command>,
 }
 
-fn main() {
-    let client = match EdifierClient::new() {
-        Ok(c) => c,
-        Err(e) => {
-            eprintln!("{e}");
-            return;
+impl Args {
+    /// Checks the options that cannot be combined.
+    fn validate(&self) -> Result<(), String> {
+        if self.record.is_some() && self.replay.is_some() {
+            return Err("Options --record and --replay cannot be used together.".to_string());
         }
-    };
 
-    /* no args */
-    if env::args().count() <= 1 {
-        run_safe_action(|| print_device_info(&client), "");
+        Ok(())
+    }
+
+    /// Whether any device status print, setting or action was requested. Connection options
+    /// like `--device` or `--timeout` alone do not count.
+    fn has_action(&self) -> bool {
+        self.info
+            || self.format.is_some()
+            || self.name.is_some()
+            || self.prompt_vol.is_some()
+            || self.game.is_some()
+            || self.ldac.is_some()
+            || self.denoise.is_some()
+            || self.equalizer.is_some()
+            || self.button.is_some()
+            || self.auto_off.is_some()
+            || self.disconnect
+            || self.power_off
+            || self.unpair
+            || self.pair
+            || self.reset
+    }
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Export(ExportCommand),
+    Import(ImportCommand),
+    Preset(PresetCommand),
+    List(ListCommand),
+    Scan(ScanCommand),
+    Raw(RawCommand),
+    Probe(ProbeCommand),
+    Logcat(LogcatCommand),
+    Btsnoop(BtsnoopCommand),
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "btsnoop",
+    description = "decode the frames exchanged over RFCOMM in a btsnoop HCI capture"
+)]
+struct BtsnoopCommand {
+    #[argh(positional, description = "btsnoop capture file path, e.g. btsnoop_hci.log")]
+    file: PathBuf,
+
+    #[argh(
+        option,
+        short = 'c',
+        description = "RFCOMM server channel of the SPP service, detected by default",
+        arg_name = "channel"
+    )]
+    channel: Option<u8>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "logcat",
+    description = "decode the frames the Android app logged under the SPPUtil tag in a logcat dump"
+)]
+struct LogcatCommand {
+    #[argh(positional, description = "logcat dump file path")]
+    file: PathBuf,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "probe",
+    description = "send every command code of a range and report the responses, \
+    commands that reset, disconnect or power off the device are always skipped"
+)]
+struct ProbeCommand {
+    #[argh(
+        option,
+        short = 's',
+        default = "0x00",
+        from_str_fn(parse_hex_byte),
+        description = "first hexadecimal command code, default 00",
+        arg_name = "code"
+    )]
+    start: u8,
+
+    #[argh(
+        option,
+        short = 'e',
+        default = "0xFF",
+        from_str_fn(parse_hex_byte),
+        description = "last hexadecimal command code, default FF",
+        arg_name = "code"
+    )]
+    end: u8,
+
+    #[argh(
+        option,
+        short = 'o',
+        description = "write the report to the file instead of the standard output",
+        arg_name = "file"
+    )]
+    output: Option<PathBuf>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "raw",
+    description = "send a command code with an optional payload and print the response frame"
+)]
+struct RawCommand {
+    #[argh(
+        positional,
+        from_str_fn(parse_hex_byte),
+        description = "hexadecimal command code",
+        arg_name = "code"
+    )]
+    code: u8,
+
+    #[argh(
+        option,
+        short = 'p',
+        from_str_fn(parse_hex_bytes),
+        description = "hexadecimal payload bytes, e.g. 0A or 0A0B",
+        arg_name = "hex"
+    )]
+    payload: Option<Vec<u8>>,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "scan",
+    description = "search for nearby Edifier devices, including the ones in pairing mode"
+)]
+struct ScanCommand {
+    #[argh(
+        option,
+        short = 't',
+        default = "10",
+        from_str_fn(parse_scan_timeout),
+        description = "inquiry duration in seconds [1-61], default 10",
+        arg_name = "seconds"
+    )]
+    timeout: u64,
+
+    #[argh(switch, short = 'j', description = "print the list as JSON")]
+    json: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "list",
+    description = "list paired and connected Edifier devices"
+)]
+struct ListCommand {
+    #[argh(switch, short = 'j', description = "print the list as JSON")]
+    json: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "export",
+    description = "save device settings to a TOML or JSON (*.json) file"
+)]
+struct ExportCommand {
+    #[argh(positional, description = "settings file path")]
+    file: PathBuf,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "preset",
+    description = "apply, list, save or delete named settings presets"
+)]
+struct PresetCommand {
+    #[argh(positional, description = "preset name")]
+    name: Option<String>,
+
+    #[argh(switch, short = 'l', description = "list saved presets")]
+    list: bool,
+
+    #[argh(
+        switch,
+        short = 's',
+        description = "save current device settings as the preset"
+    )]
+    save: bool,
+
+    #[argh(switch, short = 'd', description = "delete the preset")]
+    delete: bool,
+}
+
+#[derive(FromArgs)]
+#[argh(
+    subcommand,
+    name = "import",
+    description = "apply device settings from a TOML or JSON (*.json) file"
+)]
+struct ImportCommand {
+    #[argh(positional, description = "settings file path")]
+    file: PathBuf,
+}
+
+fn main() {
+    let mut args: Args = argh::from_env();
+    if let Err(e) = args.validate() {
+        eprintln!("{e}");
         return;
     }
 
-    let args: Args = argh::from_env();
+    if let Some(command) = args.command.take() {
+        run_command(command, &args);
+        return;
+    }
 
-    if args.info {
-        run_safe_action(|| print_device_info(&client), "");
+    let Some(mut client) = connect(&args) else {
+        return;
+    };
+
+    /* no action, print the device status */
+    if !args.has_action() {
+        print_device_info(&mut client, OutputFormat::Text);
+        return;
+    }
+
+    if args.info || args.format.is_some() {
+        print_device_info(&mut client, args.format.unwrap_or(OutputFormat::Text));
     }
 
     if args.pair {
-        run_safe_action(|| EdifierClient::pair(), "Pairing successful.");
+        run_safe_action(
+            || match &args.device {
+                Some(selector) => EdifierClient::pair_with(selector),
+                None => EdifierClient::pair(),
+            },
+            "Pairing successful.",
+        );
     }
 
     if let Some(option) = args.denoise {
@@ -159,16 +466,27 @@ fn main() {
         );
     }
 
-    /* Actions requiring device disconnection. */
+    if let Some(option) = args.auto_off {
+        run_safe_action(
+            || match option {
+                AutoPowerOff::Disabled => client.disable_auto_power_off(),
+                AutoPowerOff::Minutes(minutes) => client.set_auto_power_off(minutes),
+            },
+            &format!("Auto power-off set to: {option}."),
+        );
+    }
+
+    /* The device restarts its Bluetooth link, the client reconnects to it. */
 
     if let Some(option) = args.ldac {
-        run_unsafe_action(
+        run_safe_action(
             || client.set_ldac_mode(option),
             &format!("LDAC mode set to: {option}."),
-            args.no_confirm,
         )
     }
 
+    /* Actions requiring device disconnection. */
+
     if args.disconnect {
         run_unsafe_action(
             || client.disconnect_bluetooth(),
@@ -198,18 +516,246 @@ fn main() {
     }
 }
 
+/// Connects to the selected device, or opens the replayed session, and applies
+/// the recording, timeout and retry options.
+fn connect(args: &Args) -> Option<Client> {
+    let mut client = match open_transport(args) {
+        Ok(transport) => EdifierClient::with_transport(transport),
+        Err(e) => {
+            eprintln!("{e}");
+            return None;
+        }
+    };
+
+    if let Some(timeout) = args.timeout {
+        client.set_read_timeout(timeout);
+        client.set_write_timeout(timeout);
+    }
+    if let Some(retries) = args.retries {
+        client.set_retry_policy(RetryPolicy {
+            attempts: retries.saturating_add(1),
+            delay: RETRY_DELAY,
+        });
+    }
+
+    Some(client)
+}
+
+/// Parses a positive number of seconds, e.g. `5` or `0.5`.
+fn parse_timeout(s: &str) -> Result<Duration, String> {
+    s.trim()
+        .parse::<f64>()
+        .ok()
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .filter(|timeout| !timeout.is_zero())
+        .ok_or_else(|| format!("Timeout must be a positive number of seconds: `{s}`."))
+}
+
+/// Parses the inquiry duration in whole seconds, from 1 to `MAX_SCAN_TIMEOUT`.
+fn parse_scan_timeout(s: &str) -> Result<u64, String> {
+    s.trim()
+        .parse()
+        .ok()
+        .filter(|seconds| (1..=MAX_SCAN_TIMEOUT).contains(seconds))
+        .ok_or_else(|| format!("Timeout must be from 1 to {MAX_SCAN_TIMEOUT} seconds: `{s}`."))
+}
+
+fn open_transport(args: &Args) -> Result<Box<dyn Transport>, EdifierError> {
+    if let Some(path) = &args.replay {
+        return Ok(Box::new(ReplayTransport::open(path)?));
+    }
+
+    let transport = match &args.device {
+        Some(selector) => BluetoothTransport::connect_to(SPP_UUID, selector)?,
+        None => BluetoothTransport::connect(SPP_UUID)?,
+    };
+
+    Ok(match &args.record {
+        Some(path) => Box::new(RecordingTransport::create(transport, path)?),
+        None => Box::new(transport),
+    })
+}
+
+fn run_command(command: Command, args: &Args) {
+    match command {
+        Command::Export(command) => {
+            let Some(mut client) = connect(args) else {
+                return;
+            };
+            run_safe_action(
+                || export_settings(&mut client, &command.file),
+                &format!("Settings exported to: {}.", command.file.display()),
+            );
+        }
+        Command::Import(command) => {
+            /* Validate the file before connecting to the device. */
+            let settings = match read_settings_file(&command.file) {
+                Ok(settings) => settings,
+                Err(e) => {
+                    eprintln!("{e}");
+                    return;
+                }
+            };
+            let Some(mut client) = connect(args) else {
+                return;
+            };
+            run_safe_action(
+                || client.apply_settings(&settings),
+                &format!("Settings imported from: {}.", command.file.display()),
+            );
+        }
+        Command::Preset(command) => run_preset_command(command, args),
+        Command::List(command) => match EdifierClient::list_devices() {
+            Ok(devices) => print_devices(&devices, command.json),
+            Err(e) => eprintln!("{e}"),
+        },
+        Command::Scan(command) => {
+            match EdifierClient::scan_devices(Duration::from_secs(command.timeout)) {
+                Ok(devices) => print_devices(&devices, command.json),
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+        Command::Raw(command) => {
+            let Some(mut client) = connect(args) else {
+                return;
+            };
+            let request = EdifierMessage::new(command.code, command.payload.as_deref());
+            println!("Request:   {request}");
+            match client.send_raw(command.code, command.payload.as_deref()) {
+                Ok(response) => println!("{}", describe_message(&response)),
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+        Command::Probe(command) => run_probe_command(command, args),
+        Command::Logcat(command) => match fs::read(&command.file) {
+            Ok(bytes) => print_timeline(&parse_logcat(&String::from_utf8_lossy(&bytes))),
+            Err(e) => eprintln!("{}", EdifierError::from(e)),
+        },
+        Command::Btsnoop(command) => {
+            match fs::read(&command.file)
+                .map_err(EdifierError::from)
+                .and_then(|bytes| parse_btsnoop(&bytes, command.channel))
+            {
+                Ok(captured) => print_timeline(&captured),
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    }
+}
+
+fn print_timeline(captured: &[CapturedData]) {
+    if captured.is_empty() {
+        println!("No frames found.");
+    }
+
+    let highlight = stdout().is_terminal();
+    for data in captured {
+        print!("{}", format_timeline(data, highlight));
+    }
+}
+
+fn run_probe_command(command: ProbeCommand, args: &Args) {
+    if command.start > command.end {
+        eprintln!("Start command code must not be greater than the end one.");
+        return;
+    }
+    let Some(mut client) = connect(args) else {
+        return;
+    };
+
+    let firmware = client.get_firmware_version().ok();
+    let results = probe_commands(&mut client, command.start..=command.end, |code| {
+        eprint!("\rProbing [{code:02X}]...");
+    });
+    eprintln!();
+
+    if let Some((code, ProbeResult::Failed(e))) = results.last()
+        && *code < command.end
+    {
+        eprintln!("Probe stopped at [{code:02X}]: {e}");
+    }
+    let report = format_report(firmware.as_deref(), &results);
+
+    match command.output {
+        Some(path) => run_safe_action(
+            || Ok(fs::write(&path, report)?),
+            &format!("Probe report written to: {}.", path.display()),
+        ),
+        None => print!("{report}"),
+    }
+}
+
+fn run_preset_command(command: PresetCommand, args: &Args) {
+    let dir = match presets_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+
+    if command.list {
+        match list_presets(&dir) {
+            Ok(names) if names.is_empty() => println!("No presets saved."),
+            Ok(names) => names.iter().for_each(|name| println!("{name}")),
+            Err(e) => eprintln!("{e}"),
+        }
+        return;
+    }
+
+    let Some(name) = command.name else {
+        eprintln!("Preset name is required.");
+        return;
+    };
+
+    if command.delete {
+        run_safe_action(
+            || delete_preset(&dir, &name),
+            &format!("Preset deleted: {name}."),
+        );
+        return;
+    }
+
+    if command.save {
+        let Some(mut client) = connect(args) else {
+            return;
+        };
+        run_safe_action(
+            || save_preset(&dir, &name, &client.read_settings()?),
+            &format!("Preset saved: {name}."),
+        );
+        return;
+    }
+
+    let settings = match load_preset(&dir, &name) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("{e}");
+            return;
+        }
+    };
+    let Some(mut client) = connect(args) else {
+        return;
+    };
+    run_safe_action(
+        || client.apply_settings(&settings),
+        &format!("Preset applied: {name}."),
+    );
+}
+
 fn run_safe_action<F>(action: F, success_message: &str)
 where
-    F: FnOnce() -> Result<(), String>,
+    F: FnOnce() -> Result<(), EdifierError>,
 {
-    action()
-        .and_then(|_| Ok(println!("{success_message}")))
-        .unwrap_or_else(|e| eprintln!("{e}"));
+    match action() {
+        Ok(_) => println!("{success_message}"),
+        Err(e) => eprintln!("{e}"),
+    }
 }
 
 fn run_unsafe_action<F>(action: F, success_message: &str, skip_confirmation: bool)
 where
-    F: FnOnce() -> Result<(), String>,
+    F: FnOnce() -> Result<(), EdifierError>,
 {
     if !skip_confirmation && !confirm_disconnect() {
         println!("Operation cancelled.");
@@ -231,25 +777,56 @@ fn confirm_disconnect() -> bool {
     answer.eq_ignore_ascii_case("y") || answer.eq_ignore_ascii_case("yes")
 }
 
-fn print_device_info(client: &EdifierClient) -> Result<(), String> {
-    println!("Device name: {}", client.get_device_name()?);
-    println!("LDAC mode: {}", client.get_ldac_mode()?);
-    println!("Battery level: {}%", client.get_battery_level()?);
-    println!("Noise cancellation mode: {}", client.get_denoise_mode()?);
-    println!(
-        "Prompt voice volume: {} of {}",
-        client.get_prompt_volume()?,
-        MAX_PROMPT_VOLUME
-    );
-    println!(
-        "Control button actions: {}",
-        client.get_button_control_set()?
-    );
-    println!("Game mode: {}", client.get_game_mode()?);
-    println!("Equalizer preset: {}", client.get_equalizer_preset()?);
-    println!("Mac address: {}", client.get_mac_address()?);
-    println!("Firmware version: {}", client.get_firmware_version()?);
-    println!("Fingerprint: {}", client.get_fingerprint()?);
+#[cfg(test)]
+mod test {
+    use super::*;
 
-    Ok(())
+    fn parse(args: &[&str]) -> Args {
+        Args::from_args(&["edifier"], args).unwrap()
+    }
+
+    #[test]
+    fn test_has_action() {
+        assert!(!parse(&[]).has_action());
+        assert!(!parse(&["-m", "office", "--timeout", "5", "--retries", "2"]).has_action());
+        assert!(!parse(&["--record", "session.jsonl"]).has_action());
+        assert!(parse(&["-i"]).has_action());
+        assert!(parse(&["-f", "json"]).has_action());
+        assert!(parse(&["-m", "office", "-g", "on"]).has_action());
+        assert!(parse(&["-p"]).has_action());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(parse(&["--record", "session.jsonl"]).validate().is_ok());
+        assert!(parse(&["--replay", "session.jsonl"]).validate().is_ok());
+        assert!(parse(&["--record", "a.jsonl", "--replay", "b.jsonl"]).validate().is_err());
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("5"), Ok(Duration::from_secs(5)));
+        assert_eq!(parse_timeout("0.25"), Ok(Duration::from_millis(250)));
+        assert!(parse_timeout("0").is_err());
+        assert!(parse_timeout("0.0000000001").is_err());
+        assert!(parse_timeout("-1").is_err());
+        assert!(parse_timeout("inf").is_err());
+        assert!(parse_timeout("NaN").is_err());
+        assert!(parse_timeout("5s").is_err());
+    }
+
+    #[test]
+    fn test_parse_scan_timeout() {
+        assert_eq!(parse_scan_timeout("1"), Ok(1));
+        assert_eq!(parse_scan_timeout("61"), Ok(61));
+        assert!(parse_scan_timeout("0").is_err());
+        assert!(parse_scan_timeout("62").is_err());
+        assert!(parse_scan_timeout("-1").is_err());
+        assert!(parse_scan_timeout("2.5").is_err());
+        assert!(Args::from_args(&["edifier"], &["scan", "-t", "100"]).is_err());
+        assert!(matches!(
+            parse(&["scan", "-t", "61"]).command,
+            Some(Command::Scan(command)) if command.timeout == 61
+        ));
+    }
 }
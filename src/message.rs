@@ -1,6 +1,30 @@
-﻿use crate::utils::{join_hex, split_into_bytes};
+﻿//! The EdifierMessage struct encodes and decodes the frames of the Edifier SPP protocol.
+use crate::error::EdifierError;
+use crate::utils::{join_hex, split_into_bytes};
 use std::fmt::{Display, Formatter, Write};
 
+/// Signature of the messages sent to the device.
+pub const SIGNATURE_REQUEST: u8 = 0xAA;
+/// Signature of the device responses carrying data.
+pub const SIGNATURE_RESPONSE: u8 = 0xBB;
+/// Signature of the device acknowledgements.
+pub const SIGNATURE_ACKNOWLEDGE: u8 = 0xCC;
+
+/// Signature, length and command bytes.
+const HEADER_SIZE: usize = 3;
+/// Smallest valid message: header and CRC without payload.
+const MIN_MESSAGE_SIZE: usize = HEADER_SIZE + 2;
+/// Largest payload the length byte can count along with the command code.
+pub const MAX_PAYLOAD_SIZE: usize = 254;
+
+/// Checks if the byte is one of the frame signatures.
+pub fn is_signature(byte: u8) -> bool {
+    matches!(
+        byte,
+        SIGNATURE_REQUEST | SIGNATURE_RESPONSE | SIGNATURE_ACKNOWLEDGE
+    )
+}
+
 /// Represents an Edifier protocol message encoded as raw bytes.
 #[derive(Debug, PartialEq, Eq)]
 pub struct EdifierMessage {
@@ -11,13 +35,27 @@ impl EdifierMessage {
     
     /// Builds a new Edifier protocol message with the given command code 
     /// and optional payload.
-    pub(crate) fn new(command_code: u8, payload: Option<&[u8]>) -> Self {
+    pub fn new(command_code: u8, payload: Option<&[u8]>) -> Self {
+        Self::with_signature(SIGNATURE_REQUEST, command_code, payload)
+    }
+
+    /// Builds a new Edifier protocol message with the given signature byte, command code
+    /// and optional payload.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the payload is longer than `MAX_PAYLOAD_SIZE`.
+    pub fn with_signature(signature: u8, command_code: u8, payload: Option<&[u8]>) -> Self {
         let length = payload.map_or(0, |p| p.len());
+        assert!(
+            length <= MAX_PAYLOAD_SIZE,
+            "Payload of {length} bytes does not fit in a frame."
+        );
         let mut bytes = vec![0u8; length + 5];
         let last_index = bytes.len() - 1;
 
         /* Header */
-        bytes[0] = 0xAA;
+        bytes[0] = signature;
         bytes[1] = (length + 1) as u8;
         bytes[2] = command_code;
 
@@ -35,11 +73,8 @@ impl EdifierMessage {
     }
     
     /// Returns the message payload bytes, or `None` when the message has no payload.
-    pub(crate) fn payload(&self) -> Option<Vec<u8>> {
-        if self.bytes.is_empty() {
-            return None;
-        }
-        let pl_bytes = self.bytes[3..self.bytes.len() - 2].to_vec();
+    pub fn payload(&self) -> Option<Vec<u8>> {
+        let pl_bytes = self.bytes[HEADER_SIZE..self.bytes.len() - 2].to_vec();
         if pl_bytes.is_empty() {
             None
         } else {
@@ -47,39 +82,72 @@ impl EdifierMessage {
         }
     }
 
-    /*
-        pub(crate) fn command_code(&self) -> Option<u8> {
-            if self.bytes.is_empty() {
-                return None;
-            }
-            Some(self.bytes[2])
-        }
+    /// Returns the command code of the message.
+    pub fn command_code(&self) -> u8 {
+        self.bytes[2]
+    }
 
-        pub(crate) fn signature(&self) -> u8 {
-            self.bytes[0]
-        }
+    /// Returns the signature byte identifying the message kind.
+    pub fn signature(&self) -> u8 {
+        self.bytes[0]
+    }
 
-        pub(crate) fn data_size(&self) -> u8 {
-            self.bytes[1]
-        }
+    /// Returns the value of the length byte, which counts the command code and the payload.
+    pub fn data_size(&self) -> u8 {
+        self.bytes[1]
+    }
+
+    /// Returns the message checksum.
+    pub fn crc(&self) -> u16 {
+        u16::from_be_bytes([
+            self.bytes[self.bytes.len() - 2],
+            self.bytes[self.bytes.len() - 1],
+        ])
+    }
 
-        pub(crate) fn crc(&self) -> u16 {
-            u16::from_be_bytes([
-                self.bytes[self.bytes.len() - 2],
-                self.bytes[self.bytes.len() - 1],
-            ])
-        }
-    */
-    
     /// Returns the raw message bytes.
     pub fn as_slice(&self) -> &[u8] {
         self.bytes.as_slice()
     }
 }
 
-impl From<Vec<u8>> for EdifierMessage {
-    fn from(bytes: Vec<u8>) -> Self {
-        Self { bytes }
+impl TryFrom<Vec<u8>> for EdifierMessage {
+    type Error = EdifierError;
+
+    /// Decodes raw bytes as a message validating its signature, length and CRC.
+    fn try_from(bytes: Vec<u8>) -> Result<Self, Self::Error> {
+        if bytes.len() < MIN_MESSAGE_SIZE {
+            return Err(EdifierError::MalformedFrame(format!(
+                "too short, {} bytes",
+                bytes.len()
+            )));
+        }
+
+        let signature = bytes[0];
+        if !is_signature(signature) {
+            return Err(EdifierError::MalformedFrame(format!(
+                "invalid signature {signature:#04X}"
+            )));
+        }
+
+        let expected_size = bytes[1] as usize + MIN_MESSAGE_SIZE - 1;
+        if bytes.len() != expected_size {
+            return Err(EdifierError::MalformedFrame(format!(
+                "expected {expected_size} bytes, got {}",
+                bytes.len()
+            )));
+        }
+
+        let message = Self { bytes };
+        let expected_crc = compute_crc(&message.bytes[..message.bytes.len() - 2]);
+        if message.crc() != expected_crc {
+            return Err(EdifierError::CrcMismatch {
+                expected: expected_crc,
+                actual: message.crc(),
+            });
+        }
+
+        Ok(message)
     }
 }
 
@@ -93,7 +161,7 @@ impl Display for EdifierMessage {
 }
 
 fn compute_crc(data: &[u8]) -> u16 {
-    0x2019 + data.iter().map(|&b| b as u16).sum::<u16>()
+    data.iter().fold(0x2019, |crc: u16, &b| crc.wrapping_add(b as u16))
 }
 
 #[cfg(test)]
@@ -117,12 +185,65 @@ mod test {
     }
 
     #[test]
-    fn test_from() {
+    fn test_try_from() {
         assert_eq!(
-            EdifierMessage::from(vec![0xAA, 0x01, 0xC9, 0x21, 0x8D]),
+            EdifierMessage::try_from(vec![0xAA, 0x01, 0xC9, 0x21, 0x8D]).unwrap(),
             EdifierMessage {
                 bytes: vec![0xAA, 0x01, 0xC9, 0x21, 0x8D],
             }
         );
+        assert_eq!(
+            EdifierMessage::try_from(vec![0xCC, 0x02, 0xD2, 0x01, 0x21, 0xBA]).unwrap(),
+            EdifierMessage {
+                bytes: vec![0xCC, 0x02, 0xD2, 0x01, 0x21, 0xBA],
+            }
+        );
+    }
+
+    #[test]
+    fn test_try_from_malformed() {
+        assert!(matches!(
+            EdifierMessage::try_from(vec![0xBB, 0x02, 0xD0]),
+            Err(EdifierError::MalformedFrame(_))
+        ));
+        assert!(matches!(
+            EdifierMessage::try_from(vec![0xAB, 0x01, 0xC9, 0x21, 0x8D]),
+            Err(EdifierError::MalformedFrame(_))
+        ));
+        assert!(matches!(
+            EdifierMessage::try_from(vec![0xBB, 0x03, 0xD3, 0x00, 0x21, 0xAF]),
+            Err(EdifierError::MalformedFrame(_))
+        ));
+        assert!(matches!(
+            EdifierMessage::try_from(vec![0xBB, 0x02, 0xC3, 0x03, 0x21, 0x9D]),
+            Err(EdifierError::CrcMismatch {
+                expected: 0x219C,
+                actual: 0x219D
+            })
+        ));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let message =
+            EdifierMessage::try_from(vec![0xBB, 0x03, 0xD3, 0x00, 0x05, 0x21, 0xAF]).unwrap();
+
+        assert_eq!(message.signature(), SIGNATURE_RESPONSE);
+        assert_eq!(message.data_size(), 0x03);
+        assert_eq!(message.command_code(), 0xD3);
+        assert_eq!(message.payload(), Some(vec![0x00, 0x05]));
+        assert_eq!(message.crc(), 0x21AF);
+    }
+
+    #[test]
+    fn test_crc_overflow() {
+        let message = EdifierMessage::with_signature(SIGNATURE_RESPONSE, 0xFF, Some(&[0xFF; 254]));
+
+        assert_eq!(message.data_size(), 0xFF);
+        assert_eq!(message.crc(), 0x1FD4);
+        assert_eq!(
+            EdifierMessage::try_from(message.as_slice().to_vec()).unwrap(),
+            message
+        );
     }
 }
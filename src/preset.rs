@@ -0,0 +1,153 @@
+//! Named settings presets managed by the `preset` subcommand.
+//! Presets are stored as TOML settings files in the `edifier/presets` subdirectory
+//! of the user configuration directory.
+use crate::profile::{read_settings_file, serialize_settings};
+use edifier::{DeviceSettings, EdifierError};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const PRESET_EXTENSION: &str = "toml";
+
+/// Returns the directory holding the user presets.
+pub(crate) fn presets_dir() -> Result<PathBuf, EdifierError> {
+    dirs::config_dir()
+        .map(|dir| dir.join("edifier").join("presets"))
+        .ok_or(EdifierError::Unsupported(
+            "User configuration directory is not available.",
+        ))
+}
+
+/// Returns the names of the presets stored in the directory in alphabetical order.
+pub(crate) fn list_presets(dir: &Path) -> Result<Vec<String>, EdifierError> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut names = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == PRESET_EXTENSION)
+            && let Some(name) = path.file_stem().and_then(|s| s.to_str())
+        {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+
+    Ok(names)
+}
+
+/// Reads the preset settings.
+pub(crate) fn load_preset(dir: &Path, name: &str) -> Result<DeviceSettings, EdifierError> {
+    let path = preset_path(dir, name)?;
+    if !path.exists() {
+        return Err(EdifierError::InvalidValue(format!(
+            "Preset `{name}` does not exist."
+        )));
+    }
+
+    read_settings_file(&path)
+}
+
+/// Stores the settings as the preset, replacing the existing one. The device name is
+/// left out so that applying the preset does not rename the device.
+pub(crate) fn save_preset(
+    dir: &Path,
+    name: &str,
+    settings: &DeviceSettings,
+) -> Result<(), EdifierError> {
+    let path = preset_path(dir, name)?;
+    let settings = DeviceSettings {
+        name: None,
+        ..settings.clone()
+    };
+
+    fs::create_dir_all(dir)?;
+    fs::write(&path, serialize_settings(&settings, &path)?)?;
+
+    Ok(())
+}
+
+/// Deletes the preset.
+pub(crate) fn delete_preset(dir: &Path, name: &str) -> Result<(), EdifierError> {
+    match fs::remove_file(preset_path(dir, name)?) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Err(EdifierError::InvalidValue(
+            format!("Preset `{name}` does not exist."),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Preset names become file names, so only letters, digits, `-` and `_` are allowed.
+fn preset_path(dir: &Path, name: &str) -> Result<PathBuf, EdifierError> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if valid {
+        Ok(dir.join(name).with_extension(PRESET_EXTENSION))
+    } else {
+        Err(EdifierError::InvalidValue(format!(
+            "Invalid preset name: `{name}`. Use letters, digits, `-` and `_` only."
+        )))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use edifier::{DenoiseMode, EqualizerPreset, GameMode};
+    use std::env;
+    use std::process;
+
+    #[test]
+    fn test_presets() {
+        let dir = env::temp_dir().join(format!("edifier-presets-{}", process::id()));
+        let office = DeviceSettings {
+            name: Some("EDIFIER W820NB".to_string()),
+            denoise_mode: Some(DenoiseMode::Ambient(Some(6))),
+            game_mode: Some(GameMode::Off),
+            ..Default::default()
+        };
+        let commute = DeviceSettings {
+            denoise_mode: Some(DenoiseMode::On),
+            equalizer_preset: Some(EqualizerPreset::Pop),
+            ..Default::default()
+        };
+
+        assert_eq!(list_presets(&dir).unwrap(), Vec::<String>::new());
+
+        save_preset(&dir, "office", &office).unwrap();
+        save_preset(&dir, "commute", &commute).unwrap();
+        assert_eq!(list_presets(&dir).unwrap(), ["commute", "office"]);
+
+        let loaded = load_preset(&dir, "office").unwrap();
+        assert_eq!(loaded.name, None);
+        assert_eq!(loaded.denoise_mode, office.denoise_mode);
+
+        delete_preset(&dir, "office").unwrap();
+        assert_eq!(list_presets(&dir).unwrap(), ["commute"]);
+        assert!(load_preset(&dir, "office").is_err());
+        assert!(delete_preset(&dir, "office").is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_preset_name() {
+        let dir = Path::new("presets");
+
+        assert!(preset_path(dir, "").is_err());
+        assert!(preset_path(dir, "../office").is_err());
+        assert!(preset_path(dir, "home office").is_err());
+        assert_eq!(
+            preset_path(dir, "home_office-2").unwrap(),
+            dir.join("home_office-2.toml")
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! Command-code scan run by the `probe` subcommand.
+//! The report lists one line per command code in a stable format, so reports taken
+//! on different firmware versions can be compared with `diff`.
+use crate::raw::format_hex_bytes;
+use edifier::transport::Transport;
+use edifier::{EdifierClient, EdifierError, EdifierMessage, PROBE_DENYLIST};
+use std::fmt::Write;
+use std::ops::RangeInclusive;
+
+/// Outcome of probing a single command code.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ProbeResult {
+    Response(EdifierMessage),
+    NoResponse,
+    Skipped,
+    /// The exchange failed, the scan stops at this code when the connection is lost.
+    Failed(String),
+}
+
+/// Sends every command code of the range without a payload, skipping the denylisted ones.
+/// `progress` is called before each code is sent. A lost connection ends the scan, the
+/// results gathered so far are returned with the failing code.
+pub(crate) fn probe_commands(
+    client: &mut EdifierClient<impl Transport>,
+    codes: RangeInclusive<u8>,
+    mut progress: impl FnMut(u8),
+) -> Vec<(u8, ProbeResult)> {
+    let mut results = Vec::new();
+
+    for code in codes {
+        if PROBE_DENYLIST.contains(&code) {
+            results.push((code, ProbeResult::Skipped));
+            continue;
+        }
+
+        progress(code);
+        let result = match client.probe_command(code) {
+            Ok(Some(response)) => ProbeResult::Response(response),
+            Ok(None) => ProbeResult::NoResponse,
+            Err(e @ (EdifierError::Disconnected | EdifierError::Io(_))) => {
+                results.push((code, ProbeResult::Failed(e.to_string())));
+                break;
+            }
+            Err(e) => ProbeResult::Failed(e.to_string()),
+        };
+        results.push((code, result));
+    }
+
+    results
+}
+
+/// Formats the report, starting with the firmware version when it is known.
+pub(crate) fn format_report(firmware: Option<&str>, results: &[(u8, ProbeResult)]) -> String {
+    let mut report = String::new();
+    if let Some(firmware) = firmware {
+        writeln!(report, "# Firmware: {firmware}").unwrap();
+    }
+
+    for (code, result) in results {
+        match result {
+            ProbeResult::Response(response) => {
+                let payload = response.payload().unwrap_or_default();
+                writeln!(
+                    report,
+                    "{code:02X}: {:02X} [{}]",
+                    response.signature(),
+                    format_hex_bytes(&payload, " ")
+                )
+                .unwrap();
+            }
+            ProbeResult::NoResponse => writeln!(report, "{code:02X}: no response").unwrap(),
+            ProbeResult::Skipped => writeln!(report, "{code:02X}: skipped").unwrap(),
+            ProbeResult::Failed(e) => writeln!(report, "{code:02X}: failed: {e}").unwrap(),
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use edifier::message::{SIGNATURE_ACKNOWLEDGE, SIGNATURE_RESPONSE};
+    use edifier::{RetryPolicy, ScriptedReply, ScriptedSimulator, Simulator};
+
+    #[test]
+    fn test_probe_commands() {
+        let mut transport = ScriptedSimulator::new(Simulator::new());
+        transport.script(0x0A, ScriptedReply::Disconnect);
+        let mut client = EdifierClient::with_transport(transport);
+        client.set_retry_policy(RetryPolicy::NONE);
+
+        let results = probe_commands(&mut client, 0x05..=0x20, |_| {});
+        assert_eq!(results.len(), 6);
+        assert!(matches!(results[0], (0x05, ProbeResult::Response(_))));
+        assert_eq!(results[1], (0x06, ProbeResult::Skipped));
+        assert_eq!(
+            results[5],
+            (0x0A, ProbeResult::Failed(EdifierError::Disconnected.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_format_report() {
+        let results = [
+            (
+                0x05,
+                ProbeResult::Response(EdifierMessage::with_signature(
+                    SIGNATURE_RESPONSE,
+                    0x05,
+                    Some(&[0x08]),
+                )),
+            ),
+            (0x06, ProbeResult::NoResponse),
+            (0x07, ProbeResult::Skipped),
+            (0x0A, ProbeResult::Failed("Connection closed by device.".to_string())),
+            (
+                0xD2,
+                ProbeResult::Response(EdifierMessage::with_signature(
+                    SIGNATURE_ACKNOWLEDGE,
+                    0xD2,
+                    None,
+                )),
+            ),
+        ];
+
+        assert_eq!(
+            format_report(Some("3.0.2"), &results),
+            "# Firmware: 3.0.2\n05: BB [08]\n06: no response\n07: skipped\n\
+            0A: failed: Connection closed by device.\nD2: CC []\n"
+        );
+        assert_eq!(format_report(None, &[]), "");
+    }
+}
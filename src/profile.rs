@@ -0,0 +1,79 @@
+//! Settings profile files written by the `export` and `import` subcommands.
+//! Files with the `.json` extension hold JSON, any other file holds TOML.
+use edifier::transport::Transport;
+use edifier::{DeviceSettings, EdifierClient, EdifierError};
+use std::fs;
+use std::path::Path;
+
+/// Reads the device settings and writes them to the file.
+pub(crate) fn export_settings(
+    client: &mut EdifierClient<impl Transport>,
+    path: &Path,
+) -> Result<(), EdifierError> {
+    let settings = client.read_settings()?;
+    fs::write(path, serialize_settings(&settings, path)?)?;
+
+    Ok(())
+}
+
+/// Reads and validates the settings file.
+pub(crate) fn read_settings_file(path: &Path) -> Result<DeviceSettings, EdifierError> {
+    let text = fs::read_to_string(path)?;
+    parse_settings(&text, path)
+}
+
+/// Serializes the settings in the format matching the file extension.
+pub(crate) fn serialize_settings(
+    settings: &DeviceSettings,
+    path: &Path,
+) -> Result<String, EdifierError> {
+    let result = if is_json(path) {
+        serde_json::to_string_pretty(settings).map_err(|e| e.to_string())
+    } else {
+        toml::to_string(settings).map_err(|e| e.to_string())
+    };
+
+    result.map_err(|e| EdifierError::InvalidValue(format!("Failed to serialize settings: {e}")))
+}
+
+fn parse_settings(text: &str, path: &Path) -> Result<DeviceSettings, EdifierError> {
+    let result = if is_json(path) {
+        serde_json::from_str(text).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(text).map_err(|e| e.to_string())
+    };
+
+    result.map_err(|e| {
+        EdifierError::InvalidValue(format!("Invalid settings file `{}`: {e}", path.display()))
+    })
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("json"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use edifier::{EqualizerPreset, GameMode};
+
+    #[test]
+    fn test_settings_file_format() {
+        let settings = DeviceSettings {
+            game_mode: Some(GameMode::On),
+            equalizer_preset: Some(EqualizerPreset::Pop),
+            ..Default::default()
+        };
+
+        let toml = serialize_settings(&settings, Path::new("office.toml")).unwrap();
+        assert_eq!(toml, "game_mode = \"On\"\nequalizer_preset = \"Pop\"\n");
+
+        let json = serialize_settings(&settings, Path::new("office.JSON")).unwrap();
+        assert!(json.starts_with('{'));
+
+        assert_eq!(parse_settings(&toml, Path::new("office")).unwrap(), settings);
+        assert_eq!(parse_settings(&json, Path::new("a.json")).unwrap(), settings);
+        assert!(parse_settings(&toml, Path::new("a.json")).is_err());
+    }
+}
@@ -0,0 +1,116 @@
+//! Raw protocol frames sent and printed by the `raw` subcommand.
+//! Lets unknown commands be explored without rebuilding with the `debug` feature.
+use edifier::message::{
+    MAX_PAYLOAD_SIZE, SIGNATURE_ACKNOWLEDGE, SIGNATURE_REQUEST, SIGNATURE_RESPONSE,
+};
+use edifier::EdifierMessage;
+
+/// Parses a single hexadecimal byte, with or without the `0x` prefix.
+pub(crate) fn parse_hex_byte(s: &str) -> Result<u8, String> {
+    let digits = s.trim().trim_start_matches("0x").trim_start_matches("0X");
+    if digits.is_empty() || digits.len() > 2 {
+        return Err(format!("Invalid hexadecimal byte: `{s}`."));
+    }
+
+    u8::from_str_radix(digits, 16).map_err(|_| format!("Invalid hexadecimal byte: `{s}`."))
+}
+
+/// Formats bytes as uppercase hexadecimal pairs separated by the delimiter, the form
+/// read back by `parse_hex_bytes` for the delimiters it accepts.
+pub(crate) fn format_hex_bytes(bytes: &[u8], delimiter: &str) -> String {
+    let pairs: Vec<_> = bytes.iter().map(|b| format!("{b:02X}")).collect();
+    pairs.join(delimiter)
+}
+
+/// Parses hexadecimal bytes written together (`0A0B`) or separated by spaces, `:` or `,`.
+/// At most `MAX_PAYLOAD_SIZE` bytes are accepted, the frame cannot count more.
+pub(crate) fn parse_hex_bytes(s: &str) -> Result<Vec<u8>, String> {
+    let digits: String = s
+        .chars()
+        .filter(|c| !matches!(c, ' ' | ':' | ','))
+        .collect();
+
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err(format!("Invalid hexadecimal bytes: `{s}`."));
+    }
+    if digits.len() / 2 > MAX_PAYLOAD_SIZE {
+        return Err(format!("Payload must not exceed {MAX_PAYLOAD_SIZE} bytes."));
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i + 2], 16)
+                .map_err(|_| format!("Invalid hexadecimal bytes: `{s}`."))
+        })
+        .collect()
+}
+
+/// Describes the frame fields one per line. Frames reach here decoded, so their CRC
+/// is always valid.
+pub(crate) fn describe_message(message: &EdifierMessage) -> String {
+    let signature = message.signature();
+    let kind = match signature {
+        SIGNATURE_REQUEST => "request",
+        SIGNATURE_RESPONSE => "response",
+        SIGNATURE_ACKNOWLEDGE => "acknowledgement",
+        _ => "unknown",
+    };
+    let payload = message.payload().unwrap_or_default();
+
+    format!(
+        "Frame:     {message}\n\
+        Signature: {signature:02X} ({kind})\n\
+        Length:    {:02X}\n\
+        Command:   {:02X}\n\
+        Payload:   [{}]\n\
+        CRC:       {:04X}",
+        message.data_size(),
+        message.command_code(),
+        format_hex_bytes(&payload, ", "),
+        message.crc(),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_hex() {
+        assert_eq!(parse_hex_byte("C3"), Ok(0xC3));
+        assert_eq!(parse_hex_byte("0x68"), Ok(0x68));
+        assert_eq!(parse_hex_byte("a"), Ok(0x0A));
+        assert!(parse_hex_byte("").is_err());
+        assert!(parse_hex_byte("100").is_err());
+        assert!(parse_hex_byte("G1").is_err());
+
+        assert_eq!(parse_hex_bytes("0A"), Ok(vec![0x0A]));
+        assert_eq!(parse_hex_bytes("0a0B"), Ok(vec![0x0A, 0x0B]));
+        assert_eq!(parse_hex_bytes("0A 0B:0C,0D"), Ok(vec![0x0A, 0x0B, 0x0C, 0x0D]));
+        assert!(parse_hex_bytes("").is_err());
+        assert!(parse_hex_bytes("0A0").is_err());
+        assert!(parse_hex_bytes("ÄA").is_err());
+        assert_eq!(parse_hex_bytes(&"FF".repeat(254)).map(|b| b.len()), Ok(254));
+        assert!(parse_hex_bytes(&"FF".repeat(255)).is_err());
+
+        assert_eq!(format_hex_bytes(&[], " "), "");
+        assert_eq!(format_hex_bytes(&[0x0A, 0xBC], ", "), "0A, BC");
+        assert_eq!(parse_hex_bytes(&format_hex_bytes(&[0x0A, 0xBC], ":")), Ok(vec![0x0A, 0xBC]));
+    }
+
+    #[test]
+    fn test_describe_message() {
+        let message = EdifierMessage::with_signature(SIGNATURE_RESPONSE, 0x68, Some(&[0x00]));
+
+        assert_eq!(
+            describe_message(&message),
+            "Frame:     [BB, 02, 68, 00, 21, 3E]\n\
+            Signature: BB (response)\n\
+            Length:    02\n\
+            Command:   68\n\
+            Payload:   [00]\n\
+            CRC:       213E"
+        );
+    }
+}
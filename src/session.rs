@@ -0,0 +1,333 @@
+//! Session recording and replay.
+//! `RecordingTransport` wraps another transport and writes every frame it sends and
+//! receives to a JSON Lines file, `ReplayTransport` answers the same requests from that file,
+//! so a session taken on real hardware can run again as a deterministic test.
+use crate::error::EdifierError;
+use crate::message::EdifierMessage;
+use crate::transport::Transport;
+use crate::utils::join_hex;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, LineWriter, Write};
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Single line of a session file. Times are in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum SessionEvent {
+    /// Frame sent to the device.
+    Sent { time_ms: u64, frame: String },
+    /// Frame received from the device.
+    Received { time_ms: u64, frame: String },
+    /// No frame arrived in time.
+    Timeout { time_ms: u64 },
+    /// The device closed the connection.
+    Disconnected { time_ms: u64 },
+    /// The connection was reopened.
+    Reconnected { time_ms: u64 },
+    /// The frame recorded just before could not be sent.
+    SendFailed { time_ms: u64, error: String },
+    /// Receiving failed for another reason than a timeout or disconnection.
+    ReceiveFailed { time_ms: u64, error: String },
+}
+
+/// Transport writing the exchanged frames of the wrapped transport to a session file.
+#[derive(Debug)]
+pub struct RecordingTransport<T: Transport, W: Write = LineWriter<File>> {
+    transport: T,
+    writer: W,
+}
+
+impl<T: Transport> RecordingTransport<T> {
+    /// Creates the session file, replacing an existing one, and records the transport into it.
+    pub fn create(transport: T, path: &Path) -> Result<Self, EdifierError> {
+        Ok(Self::new(transport, LineWriter::new(File::create(path)?)))
+    }
+}
+
+impl<T: Transport, W: Write> RecordingTransport<T, W> {
+    /// Records the transport into the writer.
+    pub fn new(transport: T, writer: W) -> Self {
+        Self { transport, writer }
+    }
+
+    fn record(&mut self, event: SessionEvent) -> Result<(), EdifierError> {
+        let line = serde_json::to_string(&event).map_err(|e| {
+            EdifierError::InvalidValue(format!("Failed to serialize session event: {e}"))
+        })?;
+        writeln!(self.writer, "{line}")?;
+
+        Ok(())
+    }
+}
+
+impl<T: Transport, W: Write> Transport for RecordingTransport<T, W> {
+    /// Records the frame and sends it, then records the failure if sending failed.
+    fn send(&mut self, message: &EdifierMessage, timeout: Duration) -> Result<(), EdifierError> {
+        self.record(SessionEvent::Sent {
+            time_ms: now(),
+            frame: format_frame(message),
+        })?;
+
+        let result = self.transport.send(message, timeout);
+        if let Err(e) = &result {
+            self.record(SessionEvent::SendFailed {
+                time_ms: now(),
+                error: format_error(e),
+            })?;
+        }
+
+        result
+    }
+
+    /// Receives a frame and records it, or records the timeout or disconnection.
+    /// Polls without a timeout finding no frame are not recorded, replay answers them alike.
+    fn receive(&mut self, timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        let result = self.transport.receive(timeout);
+        let time_ms = now();
+        match &result {
+            Ok(message) => self.record(SessionEvent::Received {
+                time_ms,
+                frame: format_frame(message),
+            })?,
+            Err(EdifierError::Timeout) if timeout.is_zero() => {}
+            Err(EdifierError::Timeout) => self.record(SessionEvent::Timeout { time_ms })?,
+            Err(EdifierError::Disconnected) => {
+                self.record(SessionEvent::Disconnected { time_ms })?
+            }
+            Err(e) => self.record(SessionEvent::ReceiveFailed {
+                time_ms,
+                error: format_error(e),
+            })?,
+        }
+
+        result
+    }
+
+    /// Closes the wrapped transport and flushes the session file.
+    fn close(&mut self) {
+        self.transport.close();
+        self.writer.flush().ok();
+    }
+
+    /// Reconnects the wrapped transport and records it.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        self.transport.reconnect()?;
+        self.record(SessionEvent::Reconnected { time_ms: now() })
+    }
+}
+
+/// Transport answering requests with the responses of a recorded session.
+/// Requests must come in the recorded order.
+#[derive(Debug)]
+pub struct ReplayTransport {
+    events: VecDeque<SessionEvent>,
+}
+
+impl ReplayTransport {
+    /// Reads the session file.
+    pub fn open(path: &Path) -> Result<Self, EdifierError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    /// Parses the session file content.
+    pub fn parse(text: &str) -> Result<Self, EdifierError> {
+        let events = text
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(index, line)| {
+                serde_json::from_str(line).map_err(|e| {
+                    EdifierError::InvalidValue(format!(
+                        "Invalid session event on line {}: {e}",
+                        index + 1
+                    ))
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self { events })
+    }
+}
+
+impl Transport for ReplayTransport {
+    /// Checks that the frame is the next recorded request, and fails as recorded.
+    fn send(&mut self, message: &EdifierMessage, _timeout: Duration) -> Result<(), EdifierError> {
+        let frame = format_frame(message);
+        match self.events.front() {
+            Some(SessionEvent::Sent { frame: recorded, .. }) if *recorded == frame => {
+                self.events.pop_front();
+                match self.events.front() {
+                    Some(SessionEvent::SendFailed { error, .. }) => {
+                        let error = parse_error(error);
+                        self.events.pop_front();
+                        Err(error)
+                    }
+                    _ => Ok(()),
+                }
+            }
+            Some(SessionEvent::Sent { frame: recorded, .. }) => Err(EdifierError::InvalidValue(
+                format!("Request [{frame}] does not match the recorded request [{recorded}]."),
+            )),
+            _ => Err(EdifierError::InvalidValue(format!(
+                "Request [{frame}] was not recorded."
+            ))),
+        }
+    }
+
+    /// Returns the next recorded response, timeout, disconnection or failure.
+    fn receive(&mut self, _timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        match self.events.front() {
+            Some(SessionEvent::Received { frame, .. }) => {
+                let message = parse_frame(frame)?;
+                self.events.pop_front();
+                Ok(message)
+            }
+            Some(SessionEvent::Timeout { .. }) => {
+                self.events.pop_front();
+                Err(EdifierError::Timeout)
+            }
+            Some(SessionEvent::Disconnected { .. }) => {
+                self.events.pop_front();
+                Err(EdifierError::Disconnected)
+            }
+            Some(SessionEvent::ReceiveFailed { error, .. }) => {
+                let error = parse_error(error);
+                self.events.pop_front();
+                Err(error)
+            }
+            _ => Err(EdifierError::Timeout),
+        }
+    }
+
+    /// Nothing to close.
+    fn close(&mut self) {}
+
+    /// Checks that the session reconnected at this point.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        match self.events.front() {
+            Some(SessionEvent::Reconnected { .. }) => {
+                self.events.pop_front();
+                Ok(())
+            }
+            _ => Err(EdifierError::InvalidValue(
+                "Reconnection was not recorded.".to_string(),
+            )),
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+/// Formats the frame as space-separated hexadecimal bytes.
+fn format_frame(message: &EdifierMessage) -> String {
+    join_hex(message.as_slice(), " ")
+}
+
+/// Names the timeout and disconnection, which the client handles apart from other failures.
+fn format_error(error: &EdifierError) -> String {
+    match error {
+        EdifierError::Timeout => "timeout".to_string(),
+        EdifierError::Disconnected => "disconnected".to_string(),
+        e => e.to_string(),
+    }
+}
+
+fn parse_error(error: &str) -> EdifierError {
+    match error {
+        "timeout" => EdifierError::Timeout,
+        "disconnected" => EdifierError::Disconnected,
+        e => EdifierError::Io(io::Error::other(e)),
+    }
+}
+
+fn parse_frame(frame: &str) -> Result<EdifierMessage, EdifierError> {
+    let bytes = frame
+        .split_whitespace()
+        .map(|b| u8::from_str_radix(b, 16))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| EdifierError::MalformedFrame(format!("invalid recorded frame [{frame}]")))?;
+
+    EdifierMessage::try_from(bytes)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::device::{EdifierClient, GameMode, LdacMode, RetryPolicy};
+    use crate::simulator::{ScriptedReply, ScriptedSimulator, Simulator};
+
+    fn run_session<T: Transport>(client: &mut EdifierClient<T>) -> Result<String, EdifierError> {
+        client.set_reconnect_policy(RetryPolicy::NONE);
+        client.set_game_mode(GameMode::On)?;
+        client.set_ldac_mode(LdacMode::K48)?;
+        client.disconnect_bluetooth()?;
+
+        Ok(format!(
+            "{} {} {}",
+            client.get_device_name()?,
+            client.get_game_mode()?,
+            client.get_ldac_mode()?
+        ))
+    }
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut session = Vec::new();
+        let recorded = {
+            let transport = RecordingTransport::new(Simulator::new(), &mut session);
+            run_session(&mut EdifierClient::with_transport(transport)).unwrap()
+        };
+        let text = String::from_utf8(session).unwrap();
+
+        assert_eq!(recorded, "EDIFIER W820NB On 48K");
+        assert!(text.starts_with(r#"{"event":"sent","time_ms":"#));
+        assert!(text.lines().any(|line| line.starts_with(r#"{"event":"reconnected""#)));
+
+        let replay = ReplayTransport::parse(&text).unwrap();
+        let replayed = run_session(&mut EdifierClient::with_transport(replay)).unwrap();
+        assert_eq!(replayed, recorded);
+
+        let mut client = EdifierClient::with_transport(ReplayTransport::parse(&text).unwrap());
+        assert!(matches!(
+            client.get_battery_level(),
+            Err(EdifierError::InvalidValue(_))
+        ));
+    }
+
+    fn set_ldac_mode<T: Transport>(transport: T) -> Result<(), String> {
+        let mut client = EdifierClient::with_transport(transport);
+        client.set_reconnect_policy(RetryPolicy::NONE);
+        client.set_ldac_mode(LdacMode::K96).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn test_replay_failed_send() {
+        let mut session = Vec::new();
+        let recorded = {
+            let mut simulator = ScriptedSimulator::new(Simulator::new());
+            simulator.script(0x49, ScriptedReply::Disconnect);
+            set_ldac_mode(RecordingTransport::new(simulator, &mut session))
+        };
+        let text = String::from_utf8(session).unwrap();
+
+        assert!(recorded.is_err());
+        assert!(text.contains(r#""event":"send_failed","time_ms":"#));
+        assert!(text.contains(r#""error":"disconnected""#));
+
+        let replayed = set_ldac_mode(ReplayTransport::parse(&text).unwrap());
+        assert_eq!(replayed, recorded);
+    }
+
+    #[test]
+    fn test_invalid_session() {
+        assert!(ReplayTransport::parse("{\"event\":\"sent\"}").is_err());
+        assert!(ReplayTransport::parse("\n\n").is_ok());
+    }
+}
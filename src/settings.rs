@@ -0,0 +1,322 @@
+//! The DeviceSettings struct holds a snapshot of the user-configurable device settings,
+//! so they can be read in one call and applied back to the same or another device.
+//! Settings serialize with serde using the same value names the command line options accept.
+use crate::device::{
+    AutoPowerOff, ButtonControlSet, DenoiseMode, EdifierClient, EqualizerPreset, GameMode,
+    LdacMode, MAX_PROMPT_VOLUME,
+};
+use crate::error::EdifierError;
+use crate::transport::Transport;
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::str::FromStr;
+
+/// Snapshot of the device settings. Fields set to `None` are left unchanged when applied.
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(into = "SettingsRecord", try_from = "SettingsRecord")]
+pub struct DeviceSettings {
+    pub name: Option<String>,
+    pub prompt_volume: Option<u8>,
+    pub game_mode: Option<GameMode>,
+    pub ldac_mode: Option<LdacMode>,
+    pub denoise_mode: Option<DenoiseMode>,
+    pub equalizer_preset: Option<EqualizerPreset>,
+    pub button_control_set: Option<ButtonControlSet>,
+    pub auto_power_off: Option<AutoPowerOff>,
+}
+
+impl<T: Transport> EdifierClient<T> {
+    /// Reads all the settings from the device.
+    pub fn read_settings(&mut self) -> Result<DeviceSettings, EdifierError> {
+        Ok(DeviceSettings {
+            name: Some(self.get_device_name()?),
+            prompt_volume: Some(self.get_prompt_volume()?),
+            game_mode: Some(self.get_game_mode()?),
+            ldac_mode: Some(self.get_ldac_mode()?),
+            denoise_mode: Some(self.get_denoise_mode()?),
+            equalizer_preset: Some(self.get_equalizer_preset()?),
+            button_control_set: Some(self.get_button_control_set()?),
+            auto_power_off: Some(self.get_auto_power_off()?),
+        })
+    }
+
+    /// Applies the settings to the device, sending commands only for the fields that differ
+    /// from the current device state. LDAC mode is applied last since changing it makes
+    /// the device drop the connection.
+    pub fn apply_settings(&mut self, settings: &DeviceSettings) -> Result<(), EdifierError> {
+        if let Some(name) = &settings.name
+            && self.get_device_name()? != *name
+        {
+            self.set_device_name(name)?;
+        }
+
+        if let Some(volume) = settings.prompt_volume
+            && self.get_prompt_volume()? != volume
+        {
+            self.set_prompt_volume(volume)?;
+        }
+
+        if let Some(mode) = settings.game_mode
+            && self.get_game_mode()? != mode
+        {
+            self.set_game_mode(mode)?;
+        }
+
+        if let Some(mode) = settings.denoise_mode
+            && !same_denoise_mode(self.get_denoise_mode()?, mode)
+        {
+            self.set_denoise_mode(mode)?;
+        }
+
+        if let Some(preset) = settings.equalizer_preset
+            && self.get_equalizer_preset()? != preset
+        {
+            self.set_equalizer_preset(preset)?;
+        }
+
+        if let Some(set) = settings.button_control_set
+            && self.get_button_control_set()? != set
+        {
+            self.set_button_control_set(set)?;
+        }
+
+        if let Some(option) = settings.auto_power_off
+            && self.get_auto_power_off()? != option
+        {
+            match option {
+                AutoPowerOff::Disabled => self.disable_auto_power_off()?,
+                AutoPowerOff::Minutes(minutes) => self.set_auto_power_off(minutes)?,
+            }
+        }
+
+        /* Must be the last one, the device disconnects after the LDAC mode change. */
+        if let Some(mode) = settings.ldac_mode
+            && self.get_ldac_mode()? != mode
+        {
+            self.set_ldac_mode(mode)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Serialized form of the settings holding the values as the command line options accept them.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    prompt_volume: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ldac_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    denoise_mode: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    equalizer_preset: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    button_control_set: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    auto_power_off: Option<String>,
+}
+
+impl From<DeviceSettings> for SettingsRecord {
+    /// Values unknown to this version cannot be parsed back, so they are left out.
+    fn from(settings: DeviceSettings) -> Self {
+        Self {
+            name: settings.name,
+            prompt_volume: settings.prompt_volume,
+            game_mode: settings
+                .game_mode
+                .filter(|v| !matches!(v, GameMode::Unknown(_)))
+                .map(|v| v.to_string()),
+            ldac_mode: settings
+                .ldac_mode
+                .filter(|v| !matches!(v, LdacMode::Unknown(_)))
+                .map(|v| v.to_string()),
+            denoise_mode: settings.denoise_mode.and_then(|v| match v {
+                DenoiseMode::Off => Some("off".to_string()),
+                DenoiseMode::On => Some("on".to_string()),
+                DenoiseMode::Ambient(None) => Some("ambient".to_string()),
+                DenoiseMode::Ambient(Some(volume)) => Some(format!("ambient-{volume}")),
+                DenoiseMode::Unknown(_) => None,
+            }),
+            equalizer_preset: settings
+                .equalizer_preset
+                .filter(|v| !matches!(v, EqualizerPreset::Unknown(_)))
+                .map(|v| v.to_string()),
+            button_control_set: settings
+                .button_control_set
+                .filter(|v| !matches!(v, ButtonControlSet::Unknown(_)))
+                .map(|v| v.to_string()),
+            auto_power_off: settings.auto_power_off.map(|v| match v {
+                AutoPowerOff::Disabled => "off".to_string(),
+                AutoPowerOff::Minutes(minutes) => minutes.to_string(),
+            }),
+        }
+    }
+}
+
+impl TryFrom<SettingsRecord> for DeviceSettings {
+    type Error = EdifierError;
+
+    /// Validates the values with the same parsers and limits as the command line options.
+    fn try_from(record: SettingsRecord) -> Result<Self, Self::Error> {
+        if let Some(volume) = record.prompt_volume
+            && volume > MAX_PROMPT_VOLUME
+        {
+            return Err(EdifierError::OutOfRange {
+                name: "Prompt volume",
+                min: 0,
+                max: MAX_PROMPT_VOLUME as u32,
+            });
+        }
+
+        Ok(Self {
+            name: record.name,
+            prompt_volume: record.prompt_volume,
+            game_mode: parse_value("game_mode", record.game_mode)?,
+            ldac_mode: parse_value("ldac_mode", record.ldac_mode)?,
+            denoise_mode: parse_value("denoise_mode", record.denoise_mode)?,
+            equalizer_preset: parse_value("equalizer_preset", record.equalizer_preset)?,
+            button_control_set: parse_value("button_control_set", record.button_control_set)?,
+            auto_power_off: parse_value("auto_power_off", record.auto_power_off)?,
+        })
+    }
+}
+
+fn parse_value<T>(key: &str, value: Option<String>) -> Result<Option<T>, EdifierError>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    value
+        .map(|v| {
+            v.parse().map_err(|e| {
+                EdifierError::InvalidValue(format!("Invalid `{key}` value `{v}`: {e}"))
+            })
+        })
+        .transpose()
+}
+
+/// Ambient mode without a volume matches the ambient mode with any volume.
+fn same_denoise_mode(current: DenoiseMode, target: DenoiseMode) -> bool {
+    match (current, target) {
+        (DenoiseMode::Ambient(_), DenoiseMode::Ambient(None)) => true,
+        _ => current == target,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::device::RetryPolicy;
+    use crate::simulator::{ScriptedSimulator, Simulator};
+    use std::time::Duration;
+
+    #[test]
+    fn test_read_settings() {
+        let mut client = EdifierClient::with_transport(Simulator::new());
+
+        assert_eq!(
+            client.read_settings().unwrap(),
+            DeviceSettings {
+                name: Some("EDIFIER W820NB".to_string()),
+                prompt_volume: Some(8),
+                game_mode: Some(GameMode::Off),
+                ldac_mode: Some(LdacMode::K48),
+                denoise_mode: Some(DenoiseMode::On),
+                equalizer_preset: Some(EqualizerPreset::Default),
+                button_control_set: Some(ButtonControlSet::OnOffAmbient),
+                auto_power_off: Some(AutoPowerOff::Minutes(20)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_apply_settings() {
+        let transport = ScriptedSimulator::new(Simulator::new());
+        let requests = transport.requests();
+        let mut client = EdifierClient::with_transport(transport);
+        client.set_reconnect_policy(RetryPolicy {
+            attempts: 1,
+            delay: Duration::ZERO,
+        });
+
+        let settings = DeviceSettings {
+            ldac_mode: Some(LdacMode::K96),
+            game_mode: Some(GameMode::Off),
+            equalizer_preset: Some(EqualizerPreset::Rock),
+            auto_power_off: Some(AutoPowerOff::Disabled),
+            ..Default::default()
+        };
+        client.apply_settings(&settings).unwrap();
+
+        /* Game mode is unchanged, LDAC mode goes last and is read back after reconnecting. */
+        assert_eq!(
+            *requests.borrow(),
+            [0x08, 0xD5, 0xC4, 0xD3, 0xD2, 0x48, 0x49, 0x48]
+        );
+
+        let actual = client.read_settings().unwrap();
+        assert_eq!(actual.ldac_mode, Some(LdacMode::K96));
+        assert_eq!(actual.equalizer_preset, Some(EqualizerPreset::Rock));
+        assert_eq!(actual.auto_power_off, Some(AutoPowerOff::Disabled));
+    }
+
+    #[test]
+    fn test_serialize_settings() {
+        let settings = DeviceSettings {
+            name: Some("Office".to_string()),
+            prompt_volume: Some(5),
+            denoise_mode: Some(DenoiseMode::Ambient(Some(6))),
+            button_control_set: Some(ButtonControlSet::OffOnAmbient),
+            game_mode: Some(GameMode::Unknown(0x05)),
+            auto_power_off: Some(AutoPowerOff::Minutes(30)),
+            ..Default::default()
+        };
+
+        let json = serde_json::to_value(&settings).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": "Office",
+                "prompt_volume": 5,
+                "denoise_mode": "ambient-6",
+                "button_control_set": "Off-On-Ambient",
+                "auto_power_off": "30"
+            })
+        );
+
+        let parsed: DeviceSettings = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            parsed,
+            DeviceSettings {
+                game_mode: None,
+                ..settings
+            }
+        );
+    }
+
+    #[test]
+    fn test_deserialize_invalid_settings() {
+        assert!(toml::from_str::<DeviceSettings>("prompt_volume = 16").is_err());
+        assert!(toml::from_str::<DeviceSettings>("denoise_mode = \"ambient-13\"").is_err());
+        assert!(toml::from_str::<DeviceSettings>("game_mode = \"maybe\"").is_err());
+        assert!(toml::from_str::<DeviceSettings>("game_mode = \"Unknown (0x05)\"").is_err());
+        assert!(toml::from_str::<DeviceSettings>("auto_power_off = \"2\"").is_err());
+        assert!(toml::from_str::<DeviceSettings>("volume = 3").is_err());
+
+        assert_eq!(
+            toml::from_str::<DeviceSettings>("ldac_mode = \"96k\"\ngame_mode = \"on\"")
+                .unwrap(),
+            DeviceSettings {
+                ldac_mode: Some(LdacMode::K96),
+                game_mode: Some(GameMode::On),
+                ..Default::default()
+            }
+        );
+    }
+}
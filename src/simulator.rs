@@ -0,0 +1,294 @@
+//! The Simulator struct emulates an Edifier W820NB headset in-process.
+//! It implements the command set documented in `etc/commands.md`, keeps the device state
+//! between requests and answers with framed `BB`/`CC` responses, so the client can be
+//! exercised without hardware.
+use crate::device::{
+    CMD_DISABLE_AUTO_POWER_OFF, CMD_DISCONNECT_BLUETOOTH, CMD_GET_AUTO_POWER_OFF_TIME,
+    CMD_GET_BATTERY_LEVEL, CMD_GET_BUTTON_CONTROL_SET, CMD_GET_EQUALIZER_PRESET,
+    CMD_GET_FINGERPRINT, CMD_GET_FIRMWARE_VERSION, CMD_GET_GAME_MODE, CMD_GET_LDAC_MODE,
+    CMD_GET_MAC_ADDRESS, CMD_GET_NAME, CMD_GET_NOISE_MODE, CMD_GET_PLAYBACK_STATUS,
+    CMD_GET_PROMPT_VOLUME, CMD_POWER_OFF, CMD_RESET_FACTORY_DEFAULTS, CMD_RE_PAIR,
+    CMD_SET_AUTO_POWER_OFF_TIME, CMD_SET_BUTTON_CONTROL_SET, CMD_SET_EQUALIZER_PRESET,
+    CMD_SET_GAME_MODE, CMD_SET_LDAC_MODE, CMD_SET_NAME, CMD_SET_NOISE_MODE, CMD_SET_PROMPT_VOLUME,
+};
+use crate::error::EdifierError;
+use crate::message::{EdifierMessage, SIGNATURE_ACKNOWLEDGE, SIGNATURE_RESPONSE};
+use crate::transport::Transport;
+use std::cell::RefCell;
+use std::collections::{HashMap, VecDeque};
+use std::rc::Rc;
+use std::time::Duration;
+
+const BUTTON_CONTROL_KEY: u8 = 0x0A;
+
+const DEFAULT_NAME: &str = "EDIFIER W820NB";
+const DEFAULT_PROMPT_VOLUME: u8 = 8;
+const DEFAULT_AMBIENT_VOLUME: u8 = 6;
+const DEFAULT_BATTERY_LEVEL: u8 = 80;
+const DEFAULT_AUTO_POWER_OFF: u16 = 20;
+const MAC_ADDRESS: [u8; 6] = [0x00, 0x1E, 0x7C, 0x12, 0x34, 0x56];
+const FIRMWARE_VERSION: [u8; 3] = [0x03, 0x00, 0x02];
+const FINGERPRINT: [u8; 21] = [
+    0x57, 0x38, 0x32, 0x30, 0x4E, 0x42, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+    0x0A, 0x0B, 0x0C, 0x0D, 0x0E,
+];
+
+/// Virtual Edifier headset answering protocol requests from its in-memory state.
+#[derive(Debug)]
+pub struct Simulator {
+    name: String,
+    prompt_volume: u8,
+    game_mode: u8,
+    ldac_mode: u8,
+    noise_mode: u8,
+    ambient_volume: u8,
+    equalizer_preset: u8,
+    button_control_set: u8,
+    battery_level: u8,
+    auto_power_off: u16,
+    playback_status: u8,
+    responses: VecDeque<EdifierMessage>,
+    connected: bool,
+}
+
+impl Simulator {
+    /// Creates a simulator with factory default settings.
+    pub fn new() -> Self {
+        Self {
+            name: DEFAULT_NAME.to_string(),
+            prompt_volume: DEFAULT_PROMPT_VOLUME,
+            game_mode: 0x00,
+            ldac_mode: 0x01,
+            noise_mode: 0x02,
+            ambient_volume: DEFAULT_AMBIENT_VOLUME,
+            equalizer_preset: 0x00,
+            button_control_set: 0x07,
+            battery_level: DEFAULT_BATTERY_LEVEL,
+            auto_power_off: DEFAULT_AUTO_POWER_OFF,
+            playback_status: 0x03,
+            responses: VecDeque::new(),
+            connected: true,
+        }
+    }
+
+    /// Queues an unsolicited frame, as the device does on playback status changes.
+    pub fn notify(&mut self, message: EdifierMessage) {
+        self.responses.push_back(message);
+    }
+
+    /// Applies the request to the device state and returns the response frame, if any.
+    fn handle(&mut self, command_code: u8, payload: &[u8]) -> Option<EdifierMessage> {
+        let (signature, data) = match (command_code, payload) {
+            (CMD_GET_PROMPT_VOLUME, _) => (SIGNATURE_RESPONSE, vec![self.prompt_volume]),
+            (CMD_SET_PROMPT_VOLUME, [volume]) => {
+                self.prompt_volume = *volume;
+                (SIGNATURE_RESPONSE, vec![*volume])
+            }
+            (CMD_RESET_FACTORY_DEFAULTS, _) => {
+                *self = Self {
+                    battery_level: self.battery_level,
+                    connected: self.connected,
+                    ..Self::new()
+                };
+                return None;
+            }
+            (CMD_GET_GAME_MODE, _) => (SIGNATURE_RESPONSE, vec![self.game_mode]),
+            (CMD_SET_GAME_MODE, [mode]) => {
+                self.game_mode = *mode;
+                (SIGNATURE_RESPONSE, vec![*mode])
+            }
+            (CMD_GET_LDAC_MODE, _) => (SIGNATURE_RESPONSE, vec![self.ldac_mode]),
+            (CMD_SET_LDAC_MODE, [mode]) => {
+                /* The device restarts its Bluetooth link to apply the new mode. */
+                self.connected = false;
+                self.ldac_mode = *mode;
+                (SIGNATURE_RESPONSE, vec![*mode])
+            }
+            (CMD_SET_NOISE_MODE, [mode, rest @ ..]) => {
+                self.noise_mode = *mode;
+                if let [volume] = rest {
+                    self.ambient_volume = *volume;
+                }
+                (
+                    SIGNATURE_RESPONSE,
+                    vec![self.noise_mode, self.ambient_volume],
+                )
+            }
+            (CMD_GET_PLAYBACK_STATUS, _) => (SIGNATURE_RESPONSE, vec![self.playback_status]),
+            (CMD_SET_EQUALIZER_PRESET, [preset]) => {
+                self.equalizer_preset = *preset;
+                (SIGNATURE_ACKNOWLEDGE, vec![*preset])
+            }
+            (CMD_GET_FIRMWARE_VERSION, _) => (SIGNATURE_RESPONSE, FIRMWARE_VERSION.to_vec()),
+            (CMD_GET_MAC_ADDRESS, _) => (SIGNATURE_RESPONSE, MAC_ADDRESS.to_vec()),
+            (CMD_GET_NAME, _) => (SIGNATURE_RESPONSE, self.name.as_bytes().to_vec()),
+            (CMD_SET_NAME, name) if !name.is_empty() => {
+                self.name = String::from_utf8_lossy(name).to_string();
+                (SIGNATURE_ACKNOWLEDGE, vec![0x01])
+            }
+            (CMD_GET_NOISE_MODE, _) => (
+                SIGNATURE_RESPONSE,
+                vec![self.noise_mode, self.ambient_volume],
+            ),
+            (CMD_DISCONNECT_BLUETOOTH | CMD_POWER_OFF | CMD_RE_PAIR, _) => return None,
+            (CMD_GET_BATTERY_LEVEL, _) => (SIGNATURE_RESPONSE, vec![self.battery_level]),
+            (CMD_SET_AUTO_POWER_OFF_TIME, [high, low]) => {
+                self.auto_power_off = u16::from_be_bytes([*high, *low]);
+                (SIGNATURE_ACKNOWLEDGE, vec![0x01])
+            }
+            (CMD_DISABLE_AUTO_POWER_OFF, _) => {
+                self.auto_power_off = 0;
+                (SIGNATURE_ACKNOWLEDGE, vec![0x01])
+            }
+            (CMD_GET_AUTO_POWER_OFF_TIME, _) => (
+                SIGNATURE_RESPONSE,
+                self.auto_power_off.to_be_bytes().to_vec(),
+            ),
+            (CMD_GET_EQUALIZER_PRESET, _) => (SIGNATURE_RESPONSE, vec![self.equalizer_preset]),
+            (CMD_GET_FINGERPRINT, _) => (SIGNATURE_RESPONSE, FINGERPRINT.to_vec()),
+            (CMD_GET_BUTTON_CONTROL_SET, [BUTTON_CONTROL_KEY]) => (
+                SIGNATURE_RESPONSE,
+                vec![BUTTON_CONTROL_KEY, self.button_control_set],
+            ),
+            (CMD_SET_BUTTON_CONTROL_SET, [BUTTON_CONTROL_KEY, set]) => {
+                self.button_control_set = *set;
+                (SIGNATURE_RESPONSE, vec![BUTTON_CONTROL_KEY, *set])
+            }
+            _ => return None,
+        };
+
+        Some(EdifierMessage::with_signature(
+            signature,
+            command_code,
+            Some(&data),
+        ))
+    }
+}
+
+impl Default for Simulator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Transport for Simulator {
+    /// Processes the request and queues the response.
+    fn send(&mut self, message: &EdifierMessage, _timeout: Duration) -> Result<(), EdifierError> {
+        if !self.connected {
+            return Err(EdifierError::Disconnected);
+        }
+
+        let payload = message.payload().unwrap_or_default();
+
+        if let Some(response) = self.handle(message.command_code(), &payload) {
+            self.responses.push_back(response);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the next queued response, the responses sent before a link restart
+    /// are delivered before the disconnection is reported.
+    fn receive(&mut self, _timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        match self.responses.pop_front() {
+            Some(response) => Ok(response),
+            None if self.connected => Err(EdifierError::Timeout),
+            None => Err(EdifierError::Disconnected),
+        }
+    }
+
+    /// Drops pending responses.
+    fn close(&mut self) {
+        self.responses.clear();
+    }
+
+    /// Drops pending responses and restores the link.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        self.responses.clear();
+        self.connected = true;
+
+        Ok(())
+    }
+}
+
+/// Scripted outcome of a request sent to a `ScriptedSimulator`.
+#[derive(Debug)]
+pub enum ScriptedReply {
+    /// The frames are delivered in place of the simulator response, none drops the response.
+    Frames(Vec<EdifierMessage>),
+    /// The link drops instead of writing the request, until it is reconnected.
+    Disconnect,
+}
+
+/// Simulator whose link is scripted by tests: the command codes of the sent requests are
+/// recorded and the requests with a scripted reply do not reach the simulator.
+#[derive(Debug)]
+pub struct ScriptedSimulator {
+    simulator: Simulator,
+    requests: Rc<RefCell<Vec<u8>>>,
+    replies: HashMap<u8, VecDeque<ScriptedReply>>,
+    connected: bool,
+}
+
+impl ScriptedSimulator {
+    /// Scripts the link of the simulator.
+    pub fn new(simulator: Simulator) -> Self {
+        Self {
+            simulator,
+            requests: Rc::default(),
+            replies: HashMap::new(),
+            connected: true,
+        }
+    }
+
+    /// Adds the reply to the next request with the command code not replied yet.
+    pub fn script(&mut self, command_code: u8, reply: ScriptedReply) {
+        self.replies.entry(command_code).or_default().push_back(reply);
+    }
+
+    /// Returns the command codes of the sent requests, shared with the transport.
+    pub fn requests(&self) -> Rc<RefCell<Vec<u8>>> {
+        self.requests.clone()
+    }
+}
+
+impl Transport for ScriptedSimulator {
+    /// Records the request and queues its scripted reply, or the simulator response.
+    fn send(&mut self, message: &EdifierMessage, timeout: Duration) -> Result<(), EdifierError> {
+        let command_code = message.command_code();
+        self.requests.borrow_mut().push(command_code);
+
+        let reply = self.replies.get_mut(&command_code).and_then(VecDeque::pop_front);
+        match reply {
+            Some(ScriptedReply::Frames(frames)) if self.connected => {
+                frames.into_iter().for_each(|frame| self.simulator.notify(frame));
+                Ok(())
+            }
+            Some(ScriptedReply::Disconnect) => {
+                self.connected = false;
+                Err(EdifierError::Disconnected)
+            }
+            _ if !self.connected => Err(EdifierError::Disconnected),
+            _ => self.simulator.send(message, timeout),
+        }
+    }
+
+    /// Returns the next queued frame.
+    fn receive(&mut self, timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        if !self.connected {
+            return Err(EdifierError::Disconnected);
+        }
+
+        self.simulator.receive(timeout)
+    }
+
+    fn close(&mut self) {
+        self.simulator.close();
+    }
+
+    /// Restores the link and reconnects the simulator.
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        self.connected = true;
+        self.simulator.reconnect()
+    }
+}
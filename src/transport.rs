@@ -0,0 +1,43 @@
+//! The Transport trait abstracts the channel used by `EdifierClient` to exchange
+//! protocol frames with a device, so the same client logic can run over different backends.
+use crate::error::EdifierError;
+use crate::message::EdifierMessage;
+use std::time::Duration;
+
+/// A bidirectional channel carrying Edifier protocol frames.
+pub trait Transport {
+    /// Sends a single frame to the device, failing with `EdifierError::Timeout`
+    /// when the frame cannot be written within the timeout.
+    fn send(&mut self, message: &EdifierMessage, timeout: Duration) -> Result<(), EdifierError>;
+
+    /// Receives a single frame from the device, failing with `EdifierError::Timeout`
+    /// when no complete frame arrives within the timeout. A zero timeout returns a frame
+    /// already received without waiting.
+    fn receive(&mut self, timeout: Duration) -> Result<EdifierMessage, EdifierError>;
+
+    /// Closes the underlying connection.
+    fn close(&mut self);
+
+    /// Closes the underlying connection and opens it again to the same device,
+    /// discarding partially received frames.
+    fn reconnect(&mut self) -> Result<(), EdifierError>;
+}
+
+/// Lets the client pick the transport at runtime, e.g. `EdifierClient<Box<dyn Transport>>`.
+impl<T: Transport + ?Sized> Transport for Box<T> {
+    fn send(&mut self, message: &EdifierMessage, timeout: Duration) -> Result<(), EdifierError> {
+        (**self).send(message, timeout)
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<EdifierMessage, EdifierError> {
+        (**self).receive(timeout)
+    }
+
+    fn close(&mut self) {
+        (**self).close()
+    }
+
+    fn reconnect(&mut self) -> Result<(), EdifierError> {
+        (**self).reconnect()
+    }
+}